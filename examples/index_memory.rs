@@ -0,0 +1,110 @@
+//! Measures the memory kept by the index of a synthetic ECUC configuration.
+//!
+//! `cargo run --release --example index_memory -- [files] [containers per file] [document|stream]`
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+#[path = "../src"]
+mod src {
+    pub mod xml_parser;
+}
+
+use src::xml_parser::XmlParser;
+
+/// counts the bytes which are currently allocated
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const DEFS: &str = "/AUTOSAR/EcucDefs/Com/ComConfig/ComSignal";
+
+/// a module configuration with containers holding numerical and textual parameters and references
+fn ecuc_file(index: usize, containers: usize) -> String {
+    let mut content = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<AUTOSAR xmlns=\"http://autosar.org/schema/r4.0\">\n  <AR-PACKAGES>\n    <AR-PACKAGE>\n");
+    content.push_str(&format!("      <SHORT-NAME>EcucConfig{}</SHORT-NAME>\n      <ELEMENTS>\n", index));
+    content.push_str("        <ECUC-MODULE-CONFIGURATION-VALUES>\n          <SHORT-NAME>Com</SHORT-NAME>\n");
+    content.push_str("          <DEFINITION-REF DEST=\"ECUC-MODULE-DEF\">/AUTOSAR/EcucDefs/Com</DEFINITION-REF>\n          <CONTAINERS>\n");
+    content.push_str("            <ECUC-CONTAINER-VALUE>\n              <SHORT-NAME>ComConfig</SHORT-NAME>\n");
+    content.push_str("              <DEFINITION-REF DEST=\"ECUC-PARAM-CONF-CONTAINER-DEF\">/AUTOSAR/EcucDefs/Com/ComConfig</DEFINITION-REF>\n              <SUB-CONTAINERS>\n");
+    for container in 0..containers {
+        content.push_str(&format!("                <ECUC-CONTAINER-VALUE>\n                  <SHORT-NAME>ComSignal_{}</SHORT-NAME>\n", container));
+        content.push_str(&format!("                  <DEFINITION-REF DEST=\"ECUC-PARAM-CONF-CONTAINER-DEF\">{}</DEFINITION-REF>\n", DEFS));
+        content.push_str("                  <PARAMETER-VALUES>\n");
+        for (param, value) in [("ComBitPosition", container % 64), ("ComBitSize", 8), ("ComHandleId", container)] {
+            content.push_str(&format!("                    <ECUC-NUMERICAL-PARAM-VALUE>\n                      <DEFINITION-REF DEST=\"ECUC-INTEGER-PARAM-DEF\">{}/{}</DEFINITION-REF>\n                      <VALUE>{}</VALUE>\n                    </ECUC-NUMERICAL-PARAM-VALUE>\n", DEFS, param, value));
+        }
+        content.push_str(&format!("                    <ECUC-TEXTUAL-PARAM-VALUE>\n                      <DEFINITION-REF DEST=\"ECUC-ENUMERATION-PARAM-DEF\">{}/ComSignalEndianness</DEFINITION-REF>\n                      <VALUE>LITTLE_ENDIAN</VALUE>\n                    </ECUC-TEXTUAL-PARAM-VALUE>\n", DEFS));
+        content.push_str("                  </PARAMETER-VALUES>\n                  <REFERENCE-VALUES>\n");
+        content.push_str(&format!("                    <ECUC-REFERENCE-VALUE>\n                      <DEFINITION-REF DEST=\"ECUC-REFERENCE-DEF\">{}/ComSystemTemplateSystemSignalRef</DEFINITION-REF>\n                      <VALUE-REF DEST=\"I-SIGNAL-TO-I-PDU-MAPPING\">/Communication/Pdus/Pdu_{}/Mapping_{}</VALUE-REF>\n                    </ECUC-REFERENCE-VALUE>\n", DEFS, container / 8, container));
+        content.push_str("                  </REFERENCE-VALUES>\n                </ECUC-CONTAINER-VALUE>\n");
+    }
+    content.push_str("              </SUB-CONTAINERS>\n            </ECUC-CONTAINER-VALUE>\n          </CONTAINERS>\n        </ECUC-MODULE-CONFIGURATION-VALUES>\n");
+    content.push_str("      </ELEMENTS>\n    </AR-PACKAGE>\n  </AR-PACKAGES>\n</AUTOSAR>\n");
+    content
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let files: usize = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(20);
+    let containers: usize = args.get(2).and_then(|arg| arg.parse().ok()).unwrap_or(5000);
+    let mode = args.get(3).map(|arg| arg.as_str());
+
+    let mut content_size = 0;
+    let mut parsers = Vec::new();
+    let mut elapsed = Duration::ZERO;
+    let mut peak_parsing = 0;
+    let before = ALLOCATED.load(Ordering::Relaxed);
+
+    for index in 0..files {
+        let content = ecuc_file(index, containers);
+        content_size += content.len();
+        let mut parser = XmlParser::new(&format!("/workspace/config/ecuc/EcucConfig{}.arxml", index), true);
+
+        // the peak while parsing doesn't include the generated content
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        PEAK.store(allocated, Ordering::Relaxed);
+        let now = Instant::now();
+        match mode {
+            Some("document") => parser.parse_document(&content).expect("synthetic file must parse"),
+            Some("stream") => assert!(parser.parse_stream(content.as_bytes()), "synthetic file must be streamed"),
+            _ => parser.parse(&content).expect("synthetic file must parse"),
+        }
+        elapsed += now.elapsed();
+        peak_parsing = peak_parsing.max(PEAK.load(Ordering::Relaxed) - allocated);
+
+        drop(content);
+        parsers.push(parser);
+    }
+
+    let index_size = ALLOCATED.load(Ordering::Relaxed) - before;
+    let ident_nodes: usize = parsers.iter().map(|parser| parser.ident_nodes.len()).sum();
+    let refs: usize = parsers.iter().map(|parser| parser.refs.values().map(|refs| refs.len()).sum::<usize>()).sum();
+
+    println!("files:        {}", files);
+    println!("content:      {:.1} MB", content_size as f64 / 1e6);
+    println!("ident nodes:  {}", ident_nodes);
+    println!("refs:         {}", refs);
+    println!("index:        {:.1} MB ({} bytes per ident node)", index_size as f64 / 1e6, index_size / ident_nodes.max(1));
+    println!("parsing peak: {:.1} MB for a file, including its index", peak_parsing as f64 / 1e6);
+    println!("parsing took: {:?}", elapsed);
+}
@@ -0,0 +1,130 @@
+//! Compares the index of the streaming parser with the one of the document parser.
+//!
+//! `cargo run --example stream_equivalence -- [files or directories]`
+//!
+//! Without arguments `stream_equivalence.arxml` is compared, it contains the cases in which the parsers could
+//! differ, like text split by CDATA and comments, entities, line ends and empty elements.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use serde::Serialize;
+use serde_json::Value;
+
+#[allow(dead_code)]
+#[path = "../src"]
+mod src {
+    pub mod xml_parser;
+}
+
+use src::xml_parser::XmlParser;
+
+fn arxml_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(path).unwrap().map(|entry| entry.unwrap().path()).collect();
+        entries.sort();
+        entries.iter().for_each(|entry| arxml_files(entry, files));
+    } else if path.extension().is_some_and(|extension| extension == "arxml") {
+        files.push(path.to_path_buf());
+    }
+}
+
+/// returns the location of the first difference
+fn difference(location: String, document: &Value, stream: &Value) -> Option<String> {
+    match (document, stream) {
+        (Value::Object(document), Value::Object(stream)) => {
+            if let Some(key) = document.keys().chain(stream.keys()).find(|key| !document.contains_key(*key) || !stream.contains_key(*key)) {
+                return Some(format!("{}/{}", location, key));
+            }
+            document.iter().find_map(|(key, value)| difference(format!("{}/{}", location, key), value, &stream[key]))
+        },
+        (Value::Array(document), Value::Array(stream)) if document.len() == stream.len() => {
+            document.iter().zip(stream).enumerate().find_map(|(index, (document, stream))| difference(format!("{}[{}]", location, index), document, stream))
+        },
+        _ if document == stream => None,
+        _ => Some(format!("{}: {} != {}", location, document, stream)),
+    }
+}
+
+fn value<T: Serialize>(node: &T) -> Value {
+    serde_json::to_value(node).unwrap()
+}
+
+/// compares the nodes one by one, the whole index as a single value could be larger than the memory
+fn compare(document: &XmlParser, stream: &XmlParser) -> Option<String> {
+    let paths = |parser: &XmlParser| parser.ident_nodes.keys().cloned().collect::<Vec<_>>();
+    if paths(document) != paths(stream) {
+        return difference("/ident_nodes".to_string(), &value(&paths(document)), &value(&paths(stream)));
+    }
+    for (path, node) in document.ident_nodes.iter() {
+        if let Some(difference) = difference(format!("/ident_nodes/{}", path), &value(node), &value(&stream.ident_nodes[path])) {
+            return Some(difference);
+        }
+    }
+    if let Some(difference) = difference("/duplicate_nodes".to_string(), &value(&document.duplicate_nodes), &value(&stream.duplicate_nodes)) {
+        return Some(difference);
+    }
+
+    let mut texts = document.refs.keys().collect::<Vec<_>>();
+    texts.sort();
+    let mut stream_texts = stream.refs.keys().collect::<Vec<_>>();
+    stream_texts.sort();
+    if texts != stream_texts {
+        return difference("/refs".to_string(), &value(&texts), &value(&stream_texts));
+    }
+    for text in texts {
+        if let Some(difference) = difference(format!("/refs/{}", text), &value(&document.refs[text]), &value(&stream.refs[text])) {
+            return Some(difference);
+        }
+    }
+
+    let blocks = |parser: &XmlParser| {
+        let mut blocks = parser.blocks.clone();
+        blocks.sort_by_key(|block| block.start);
+        blocks
+    };
+    if let Some(difference) = difference("/blocks".to_string(), &value(&blocks(document)), &value(&blocks(stream))) {
+        return Some(difference);
+    }
+
+    difference("/vendor_mapping".to_string(), &value(&document.vendor_mapping), &value(&stream.vendor_mapping))
+}
+
+fn main() -> ExitCode {
+    let mut files = Vec::new();
+    std::env::args().skip(1).for_each(|arg| arxml_files(Path::new(&arg), &mut files));
+    if files.is_empty() {
+        files.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/stream_equivalence.arxml"));
+    }
+
+    let mut failed = false;
+    for file in files {
+        let file_name = file.to_str().unwrap();
+        let content = std::fs::read_to_string(&file).unwrap();
+
+        let mut document = XmlParser::new(file_name, true);
+        let document_result = document.parse_document(&content);
+        let mut stream = XmlParser::new(file_name, true);
+        let streamed = stream.parse_stream(content.as_bytes());
+
+        match (document_result, streamed) {
+            (Ok(_), true) => {
+                match compare(&document, &stream) {
+                    Some(difference) => {
+                        println!("{}: differs at {}", file_name, difference);
+                        failed = true;
+                    },
+                    None => println!("{}: identical", file_name),
+                }
+            },
+            (Ok(_), false) => println!("{}: parsed as document", file_name),
+            (Err(e), false) => println!("{}: rejected by both ({})", file_name, e),
+            (Err(e), true) => {
+                println!("{}: only rejected by the document parser ({})", file_name, e);
+                failed = true;
+            },
+        }
+    }
+
+    if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::*;
+use tower_lsp::jsonrpc::Result;
+
+use super::Backend;
+
+pub async fn code_action(_backend: &Backend, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+    let mut actions = Vec::new();
+
+    for diagnostic in params.context.diagnostics.iter() {
+        if diagnostic.code != Some(NumberOrString::String("dest-mismatch".to_string())) {
+            continue;
+        }
+
+        // the diagnostic carries the tags of the referenced elements
+        let tags = match diagnostic.data.as_ref().and_then(|data| data.as_array()) {
+            Some(tags) => tags,
+            None => continue,
+        };
+
+        for tag in tags.iter().filter_map(|tag| tag.as_str()) {
+            let edit = TextEdit {
+                range: diagnostic.range,
+                new_text: tag.to_string(),
+            };
+
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Change DEST to {}", tag),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(params.text_document.uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(tags.len() == 1),
+                ..Default::default()
+            }));
+        }
+    }
+
+    Ok(Some(actions))
+}
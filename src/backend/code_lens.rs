@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+use tower_lsp::jsonrpc::{Error, Result};
+
+use crate::xml_parser::{is_ecuc_def, PositionEncoding};
+
+use super::{Backend, Parsers};
+
+/// identifies the element of a code lens until it is resolved
+#[derive(Serialize, Deserialize)]
+struct CodeLensData {
+    file: String,
+    path: String,
+    /// the lens counts the instances of a definition instead of the references
+    instances: bool,
+}
+
+/// lists a lens with the references of every identifiable element and one with the configured instances of every
+/// ECUC definition element. Counting the references needs all parsers, so it is only done for the lenses the client
+/// resolves.
+pub async fn code_lens(backend: &Backend, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+    let file_path = params.text_document.uri.to_file_path().unwrap();
+    let file_name = file_path.to_str().unwrap();
+
+    let parsers = backend.parsers.read().unwrap();
+    let parser = match parsers.get(file_name) {
+        Some(parser) => parser,
+        None => return Ok(None),
+    };
+
+    let mut lenses = Vec::new();
+    for node in parser.ident_nodes.values().chain(parser.duplicate_nodes.iter()) {
+        let range = parser.lsp_range(&node.node.range, backend.position_encoding);
+        let mut push = |instances: bool| {
+            let data = CodeLensData { file: file_name.to_string(), path: node.path.to_string(), instances };
+            lenses.push(CodeLens { range, command: None, data: serde_json::to_value(data).ok() });
+        };
+        push(false);
+        if is_instantiable(&node.node.tag_name) {
+            push(true);
+        }
+    }
+
+    Ok(Some(lenses))
+}
+
+pub async fn code_lens_resolve(backend: &Backend, mut lens: CodeLens) -> Result<CodeLens> {
+    let data: CodeLensData = match lens.data.take().and_then(|data| serde_json::from_value(data).ok()) {
+        Some(data) => data,
+        None => return Err(Error::invalid_params("code lens without data")),
+    };
+
+    let parsers = backend.parsers.read().unwrap();
+    let locations = ref_locations(&parsers, &data, backend.position_encoding);
+
+    let title = match (data.instances, locations.len()) {
+        (false, 1) => "1 reference".to_string(),
+        (false, count) => format!("{} references", count),
+        (true, 1) => "1 configured instance".to_string(),
+        (true, count) => format!("{} configured instances", count),
+    };
+    let uri = Url::from_file_path(&data.file).unwrap();
+    lens.command = Some(Command {
+        title,
+        command: "editor.action.showReferences".to_string(),
+        arguments: Some(vec![
+            serde_json::json!(uri),
+            serde_json::json!(lens.range.start),
+            serde_json::json!(locations),
+        ]),
+    });
+
+    Ok(lens)
+}
+
+/// definition elements which are instantiated in a configuration by elements with a DEFINITION-REF, the literals
+/// of an enumeration are referred to by the value instead
+fn is_instantiable(tag_name: &str) -> bool {
+    is_ecuc_def(tag_name) && tag_name != "ECUC-ENUMERATION-LITERAL-DEF"
+}
+
+/// returns the locations of the references to the element, or of the DEFINITION-REFs of its instances. References into
+/// a refined module are found with the vendor mapping of the element's file.
+fn ref_locations(parsers: &Parsers, data: &CodeLensData, encoding: PositionEncoding) -> Vec<Location> {
+    let mut paths = vec![data.path.clone()];
+    if let Some((module, refined_module)) = parsers.get(&data.file).and_then(|parser| parser.vendor_mapping.as_ref()) {
+        if let Some(rest) = data.path.strip_prefix(module.as_str()) {
+            if rest.is_empty() || rest.starts_with('/') {
+                paths.push(format!("{}{}", refined_module, rest));
+            }
+        }
+    }
+
+    let mut locations = Vec::new();
+    for parser in parsers.values() {
+        for ref_node in paths.iter().filter_map(|path| parser.refs.get(path.as_str())).flatten() {
+            if data.instances && ref_node.tag_name != "DEFINITION-REF" {
+                continue;
+            }
+            locations.push(Location {
+                uri: Url::from_file_path(ref_node.file.as_str()).unwrap(),
+                range: parser.lsp_range(&ref_node.range, encoding),
+            });
+        }
+    }
+    locations
+}
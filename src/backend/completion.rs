@@ -0,0 +1,363 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use tower_lsp::lsp_types::*;
+use tower_lsp::jsonrpc::Result;
+
+use crate::xml_parser::{IdentNode, PositionEncoding, RefNode, ValueNode, XmlParser};
+
+use super::{find_ident_nodes, Backend, Parsers};
+
+pub async fn completion(backend: &Backend, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+    let position = params.text_document_position.position;
+    let file_path = params.text_document_position.text_document.uri.to_file_path().unwrap();
+    let file_name = file_path.to_str().unwrap();
+
+    let parsers = backend.parsers.read().unwrap();
+    if let Some(parser) = parsers.get(file_name) {
+        if let Some((ref_node, cursor_pos)) = parser.get_ref_node_at(position, backend.position_encoding) {
+            return Ok(Some(CompletionResponse::Array(reference_completion(&parsers, parser, ref_node, cursor_pos, backend.position_encoding))));
+        }
+        if let Some((value_node, _)) = parser.get_value_node_at(position, backend.position_encoding) {
+            if value_node.typ == SymbolKind::ENUM {
+                return Ok(Some(CompletionResponse::Array(enum_completion(&parsers, parser, value_node, position, backend.position_encoding))));
+            }
+        }
+        if let Some(node) = parser.get_ident_node_at(position, backend.position_encoding) {
+            if node.node.tag_name == "ECUC-CONTAINER-VALUE" || node.node.tag_name == "ECUC-MODULE-CONFIGURATION-VALUES" {
+                // the parser doesn't keep the content, it is taken from the open document or read again
+                let text = match backend.documents.get(file_name) {
+                    Some(document) => Cow::Borrowed(document.text.as_str()),
+                    None => match parser.read_content() {
+                        Ok(text) => Cow::Owned(text),
+                        Err(_) => return Ok(None),
+                    },
+                };
+                let insertion = parser.offset(position, backend.position_encoding)
+                    .and_then(|cursor| insertion_at(&text, node, cursor));
+                if let Some(insertion) = insertion {
+                    return Ok(Some(CompletionResponse::Array(container_completion(&parsers, parser, node, &insertion))));
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// completes the path segment at the cursor with the elements which are, or contain, elements of the DEST type
+fn reference_completion(parsers: &Parsers, parser: &XmlParser, ref_node: &RefNode, cursor_pos: usize, encoding: PositionEncoding) -> Vec<CompletionItem> {
+    let typed = ref_node.text.get(..cursor_pos).unwrap_or(&ref_node.text);
+    let (parent_path, segment) = typed.rsplit_once('/').unwrap_or(("", typed));
+    let prefix = format!("{}/", parent_path);
+
+    let cursor = ref_node.text_range.start + cursor_pos;
+    let range = parser.lsp_range(&(cursor - segment.len()..cursor), encoding);
+
+    let mut items: BTreeMap<&str, CompletionItem> = BTreeMap::new();
+
+    for parser in parsers.values() {
+        let mut prefixes = vec![prefix.clone()];
+        if let Some(vendor_mapping) = &parser.vendor_mapping {
+            if prefix.starts_with(vendor_mapping.1.as_str()) {
+                prefixes.push(prefix.replacen(vendor_mapping.1.as_str(), vendor_mapping.0.as_str(), 1));
+            }
+        }
+
+        for prefix in prefixes.iter() {
+            for node in parser.get_nodes_with_prefix(prefix) {
+                if !ref_node.dest.is_empty() && node.node.tag_name != ref_node.dest {
+                    continue;
+                }
+
+                let name = node.path[prefix.len()..].split('/').next().unwrap();
+                let is_target = name.len() == node.path.len() - prefix.len();
+
+                if is_target {
+                    items.insert(name, CompletionItem {
+                        label: name.to_string(),
+                        kind: Some(CompletionItemKind::REFERENCE),
+                        detail: Some(node.node.tag_name.to_string()),
+                        text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text: name.to_string() })),
+                        ..Default::default()
+                    });
+                } else {
+                    // elements on the way to a target continue the path
+                    items.entry(name).or_insert_with(|| CompletionItem {
+                        label: name.to_string(),
+                        kind: Some(CompletionItemKind::FOLDER),
+                        detail: Some(node.path[..prefix.len() + name.len()].to_string()),
+                        text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text: format!("{}/", name) })),
+                        command: Some(Command {
+                            title: "continue path".to_string(),
+                            command: "editor.action.triggerSuggest".to_string(),
+                            arguments: None,
+                        }),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+
+    items.into_values().collect()
+}
+
+/// completes the value of an enumeration parameter with the literals of its definition
+fn enum_completion(parsers: &Parsers, parser: &XmlParser, value_node: &ValueNode, position: Position, encoding: PositionEncoding) -> Vec<CompletionItem> {
+    // a self-closing value element has no content which could be replaced
+    if value_node.value.is_empty() && !value_node.value_range.is_empty() {
+        return Vec::new();
+    }
+
+    let range = if value_node.value.is_empty() {
+        Range { start: position, end: position }
+    } else {
+        parser.lsp_range(&value_node.value_range, encoding)
+    };
+
+    let mut items: BTreeMap<&str, CompletionItem> = BTreeMap::new();
+
+    let def_ref = value_node.node.def_ref.as_deref().unwrap_or("");
+    for definition in find_ident_nodes(parsers, def_ref) {
+        let default_value = definition.param_def.as_ref().and_then(|param_def| param_def.default_value.as_deref());
+        let prefix = format!("{}/", definition.path);
+
+        for parser in parsers.values() {
+            for literal in parser.get_nodes_with_prefix(&prefix) {
+                if literal.node.tag_name != "ECUC-ENUMERATION-LITERAL-DEF" || literal.path[prefix.len()..].contains('/') {
+                    continue;
+                }
+
+                let is_default = default_value == Some(literal.short_name.as_str());
+                items.insert(&literal.short_name, CompletionItem {
+                    label: literal.short_name.to_string(),
+                    kind: Some(CompletionItemKind::ENUM_MEMBER),
+                    detail: Some(if is_default { format!("{} (default)", definition.short_name) } else { definition.short_name.to_string() }),
+                    documentation: literal.desc.as_ref().map(|desc| Documentation::MarkupContent(MarkupContent {
+                        kind: MarkupKind::PlainText,
+                        value: desc.to_string(),
+                    })),
+                    preselect: Some(is_default),
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text: literal.short_name.to_string() })),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    items.into_values().collect()
+}
+
+/// sections of a container in the order of the schema, the other elements of a container precede them
+const SECTIONS: [&str; 4] = ["PARAMETER-VALUES", "REFERENCE-VALUES", "SUB-CONTAINERS", "CONTAINERS"];
+
+/// where the cursor is inside of a container, the elements of a container can only be added inside of their section
+enum Insertion {
+    /// between the child elements of the container, which are split by the cursor
+    Container { before: Vec<String>, after: Vec<String> },
+    /// inside of a section like PARAMETER-VALUES
+    Section(String),
+}
+
+impl Insertion {
+    /// returns the snippet for the section, wrapped in the section element if it can be added at the cursor
+    fn snippet(&self, section: &str, snippet: String) -> Option<String> {
+        match self {
+            Insertion::Section(name) if name == section => Some(snippet),
+            Insertion::Section(_) => None,
+            Insertion::Container { before, after } => {
+                let rank = |name: &str| SECTIONS.iter().position(|known| *known == name).map_or(0, |index| index + 1);
+                let section_rank = rank(section);
+                // a section exists only once and has to keep the order of the schema
+                if before.iter().any(|name| rank(name) >= section_rank) || after.iter().any(|name| rank(name) <= section_rank) {
+                    return None;
+                }
+                Some(format!("<{}>\n\t{}\n</{}>", section, snippet.replace('\n', "\n\t"), section))
+            },
+        }
+    }
+}
+
+/// determines the element around the cursor from the content of the container up to the cursor. Returns `None` if
+/// the cursor is inside of a tag, a comment, or another element than the container or one of its sections.
+fn insertion_at(text: &str, container: &IdentNode, cursor: usize) -> Option<Insertion> {
+    let range = &container.node.range;
+    if cursor <= range.start || cursor >= range.end {
+        return None;
+    }
+
+    let mut open = Vec::new();
+    let mut reader = Reader::from_str(text.get(range.start..cursor)?);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) => open.push(String::from_utf8_lossy(start.local_name().as_ref()).into_owned()),
+            Event::End(_) => {
+                open.pop()?;
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    match open.len() {
+        1 => {
+            let (before, after) = child_elements(text.get(range.clone())?).into_iter()
+                .partition(|(_, start)| range.start + start < cursor);
+            let names = |children: Vec<(String, usize)>| children.into_iter().map(|(name, _)| name).collect();
+            Some(Insertion::Container { before: names(before), after: names(after) })
+        },
+        2 => open.pop().map(Insertion::Section),
+        _ => None,
+    }
+}
+
+/// returns the tag names and offsets of the child elements of the element
+fn child_elements(element: &str) -> Vec<(String, usize)> {
+    let mut children = Vec::new();
+    let mut depth = 0;
+    let mut reader = Reader::from_str(element);
+    loop {
+        let start = reader.buffer_position();
+        match reader.read_event() {
+            Ok(Event::Start(child)) => {
+                if depth == 1 {
+                    children.push((String::from_utf8_lossy(child.local_name().as_ref()).into_owned(), start));
+                }
+                depth += 1;
+            },
+            Ok(Event::Empty(child)) if depth == 1 => children.push((String::from_utf8_lossy(child.local_name().as_ref()).into_owned(), start)),
+            Ok(Event::End(_)) => depth -= 1,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => (),
+        }
+    }
+    children
+}
+
+/// returns the section of a container which holds the elements with the tag name
+fn section(container_tag: &str, value_tag: &str) -> &'static str {
+    match value_tag {
+        "ECUC-NUMERICAL-PARAM-VALUE" | "ECUC-TEXTUAL-PARAM-VALUE" => "PARAMETER-VALUES",
+        "ECUC-REFERENCE-VALUE" => "REFERENCE-VALUES",
+        _ if container_tag == "ECUC-MODULE-CONFIGURATION-VALUES" => "CONTAINERS",
+        _ => "SUB-CONTAINERS",
+    }
+}
+
+/// offers snippets for the parameters, references and sub-containers which can still be added to the container at
+/// the insertion
+fn container_completion(parsers: &Parsers, parser: &XmlParser, container: &IdentNode, insertion: &Insertion) -> Vec<CompletionItem> {
+    let def_ref = match &container.node.def_ref {
+        Some(def_ref) => def_ref,
+        None => return Vec::new(),
+    };
+
+    // instances are matched to their definition by the last segment of the DEFINITION-REF
+    let mut instances: HashMap<&str, u32> = HashMap::new();
+    let prefix = format!("{}/", container.path);
+    for sub_container in parser.get_nodes_with_prefix(&prefix) {
+        if let Some(sub_def_ref) = &sub_container.node.def_ref {
+            if !sub_container.path[prefix.len()..].contains('/') {
+                *instances.entry(sub_def_ref.rsplit('/').next().unwrap()).or_default() += 1;
+            }
+        }
+    }
+    for value in container.values.iter() {
+        *instances.entry(value.short_name.as_str()).or_default() += 1;
+    }
+
+    let mut items: BTreeMap<&str, CompletionItem> = BTreeMap::new();
+
+    for definition in find_ident_nodes(parsers, def_ref) {
+        let def_prefix = format!("{}/", definition.path);
+
+        for def_parser in parsers.values() {
+            for child_definition in def_parser.get_nodes_with_prefix(&def_prefix) {
+                if child_definition.path[def_prefix.len()..].contains('/') {
+                    continue;
+                }
+
+                let (lower, upper) = match &child_definition.multiplicity {
+                    Some(multiplicity) => (multiplicity.lower, multiplicity.upper),
+                    None => (1, Some(1)),
+                };
+                let count = instances.get(child_definition.short_name.as_str()).copied().unwrap_or(0);
+                if upper.is_some_and(|upper| count >= upper) {
+                    continue;
+                }
+
+                // the DEFINITION-REF continues the path the container uses for its definition
+                let child_def_ref = format!("{}/{}", def_ref, child_definition.short_name);
+                let (snippet, kind) = match get_value_snippet(child_definition, &child_def_ref) {
+                    Some((value_tag, snippet, kind)) => match insertion.snippet(section(&container.node.tag_name, value_tag), snippet) {
+                        Some(snippet) => (snippet, kind),
+                        None => continue,
+                    },
+                    None => continue,
+                };
+
+                items.insert(&child_definition.short_name, CompletionItem {
+                    label: child_definition.short_name.to_string(),
+                    kind: Some(kind),
+                    detail: Some(child_definition.node.tag_name.to_string()),
+                    documentation: child_definition.desc.as_ref().map(|desc| Documentation::MarkupContent(MarkupContent {
+                        kind: MarkupKind::PlainText,
+                        value: desc.to_string(),
+                    })),
+                    // mandatory elements are listed first
+                    sort_text: Some(format!("{}{}", if count < lower { 0 } else { 1 }, child_definition.short_name)),
+                    insert_text: Some(snippet),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    items.into_values().collect()
+}
+
+/// returns the tag name and the XML snippet of the value element for a definition
+fn get_value_snippet(definition: &IdentNode, def_ref: &str) -> Option<(&'static str, String, CompletionItemKind)> {
+    let tag_name = definition.node.tag_name.as_str();
+    let default_value = definition.param_def.as_ref()
+        .and_then(|param_def| param_def.default_value.as_deref())
+        .unwrap_or("");
+
+    let (value_tag, value, kind) = match tag_name {
+        "ECUC-INTEGER-PARAM-DEF" | "ECUC-FLOAT-PARAM-DEF" | "ECUC-BOOLEAN-PARAM-DEF" => {
+            ("ECUC-NUMERICAL-PARAM-VALUE", format!("<VALUE>${{1:{}}}</VALUE>", escape_snippet(default_value)), CompletionItemKind::FIELD)
+        },
+        "ECUC-ENUMERATION-PARAM-DEF" | "ECUC-STRING-PARAM-DEF" | "ECUC-FUNCTION-NAME-DEF" | "ECUC-LINKER-SYMBOL-DEF" | "ECUC-MULTILINE-STRING-PARAM-DEF" => {
+            ("ECUC-TEXTUAL-PARAM-VALUE", format!("<VALUE>${{1:{}}}</VALUE>", escape_snippet(default_value)), CompletionItemKind::FIELD)
+        },
+        "ECUC-REFERENCE-DEF" | "ECUC-CHOICE-REFERENCE-DEF" | "ECUC-SYMBOLIC-NAME-REFERENCE-DEF" => {
+            ("ECUC-REFERENCE-VALUE", "<VALUE-REF DEST=\"ECUC-CONTAINER-VALUE\">$1</VALUE-REF>".to_string(), CompletionItemKind::REFERENCE)
+        },
+        "ECUC-FOREIGN-REFERENCE-DEF" => {
+            ("ECUC-REFERENCE-VALUE", "<VALUE-REF DEST=\"${1:DEST}\">$2</VALUE-REF>".to_string(), CompletionItemKind::REFERENCE)
+        },
+        "ECUC-PARAM-CONF-CONTAINER-DEF" | "ECUC-CHOICE-CONTAINER-DEF" => {
+            return Some((
+                "ECUC-CONTAINER-VALUE",
+                format!("<ECUC-CONTAINER-VALUE>\n\t<SHORT-NAME>${{1:{}}}</SHORT-NAME>\n\t<DEFINITION-REF DEST=\"{}\">{}</DEFINITION-REF>\n</ECUC-CONTAINER-VALUE>",
+                    escape_snippet(&definition.short_name), tag_name, escape_snippet(def_ref)),
+                CompletionItemKind::STRUCT,
+            ));
+        },
+        _ => return None,
+    };
+
+    Some((
+        value_tag,
+        format!("<{}>\n\t<DEFINITION-REF DEST=\"{}\">{}</DEFINITION-REF>\n\t{}\n</{}>", value_tag, tag_name, escape_snippet(def_ref), value, value_tag),
+        kind,
+    ))
+}
+
+fn escape_snippet(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('$', "\\$").replace('}', "\\}")
+}
@@ -0,0 +1,509 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use anyhow::Error;
+use regex::Regex;
+use tower_lsp::lsp_types::*;
+use tower_lsp::Client;
+
+use crate::xml_parser::{IdentNode, LineIndex, PositionEncoding, ValueNode};
+
+use super::{Backend, Parsers};
+
+const SOURCE: &str = "arxml";
+
+/// time without further changes after which the diagnostics of a change are published
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(300);
+
+/// elements which may be declared multiple times with the same path, their contents are merged. The ECUC values are
+/// atpSplitable, e.g. the containers of a module configuration may be spread across files.
+const SPLITABLE_ELEMENTS: [&str; 3] = ["AR-PACKAGE", "ECUC-MODULE-CONFIGURATION-VALUES", "ECUC-CONTAINER-VALUE"];
+
+/// all declarations of the workspace, used to resolve references without querying every parser
+struct WorkspaceIndex<'a> {
+    declarations: HashMap<&'a str, Vec<&'a IdentNode>>,
+    children: HashMap<&'a str, Vec<&'a IdentNode>>,
+    vendor_mappings: Vec<&'a (String, String)>,
+}
+
+impl<'a> WorkspaceIndex<'a> {
+    fn new(parsers: &'a Parsers) -> Self {
+        let mut declarations: HashMap<&str, Vec<&IdentNode>> = HashMap::new();
+        let mut children: HashMap<&str, Vec<&IdentNode>> = HashMap::new();
+
+        for parser in parsers.values() {
+            for node in parser.ident_nodes.values().chain(parser.duplicate_nodes.iter()) {
+                declarations.entry(node.path.as_str()).or_default().push(node);
+                if let Some((parent_path, _)) = node.path.rsplit_once('/') {
+                    children.entry(parent_path).or_default().push(node);
+                }
+            }
+        }
+
+        let vendor_mappings = parsers.values()
+            .filter_map(|parser| parser.vendor_mapping.as_ref())
+            .collect();
+
+        WorkspaceIndex {
+            declarations,
+            children,
+            vendor_mappings,
+        }
+    }
+
+    fn resolve(&self, path: &str) -> Vec<&'a IdentNode> {
+        let mut nodes = Vec::new();
+
+        if let Some(declarations) = self.declarations.get(path) {
+            nodes.extend(declarations);
+        }
+
+        // references into a refined module definition point to the standardized path
+        for vendor_mapping in self.vendor_mappings.iter() {
+            if path.starts_with(vendor_mapping.1.as_str()) {
+                if let Some(declarations) = self.declarations.get(path.replacen(vendor_mapping.1.as_str(), vendor_mapping.0.as_str(), 1).as_str()) {
+                    nodes.extend(declarations);
+                }
+            }
+        }
+
+        nodes
+    }
+
+    fn children(&self, path: &str) -> &[&'a IdentNode] {
+        self.children.get(path).map_or(&[], |children| children.as_slice())
+    }
+
+    /// resolves the DEFINITION-REF of an ECUC value to its definition element
+    fn definition(&self, def_ref: &str) -> Option<&'a IdentNode> {
+        self.resolve(def_ref).into_iter().find(|node| node.node.tag_name.ends_with("-DEF"))
+    }
+}
+
+pub async fn publish_diagnostics(backend: &Backend) {
+    publish(&backend.client, &backend.parsers, &backend.parse_errors, &backend.published_diagnostics, backend.position_encoding, &|| false).await;
+}
+
+/// publishes the diagnostics in the background once the documents weren't changed for a moment, the diagnostics of
+/// the whole workspace are only checked once while typing. The change has to be counted with `count_change` before
+/// the index is updated.
+pub fn publish_diagnostics_debounced(backend: &Backend) {
+    let change = backend.changes.load(Ordering::SeqCst);
+
+    let changes = backend.changes.clone();
+    let client = backend.client.clone();
+    let parsers = backend.parsers.clone();
+    let parse_errors = backend.parse_errors.clone();
+    let published_diagnostics = backend.published_diagnostics.clone();
+    let position_encoding = backend.position_encoding;
+
+    tokio::spawn(async move {
+        tokio::time::sleep(DEBOUNCE_DELAY).await;
+        let superseded = || changes.load(Ordering::SeqCst) != change;
+        if !superseded() {
+            publish(&client, &parsers, &parse_errors, &published_diagnostics, position_encoding, &superseded).await;
+        }
+    });
+}
+
+/// counts a change of a document. A debounced publishing of an earlier change is dropped, and if it is already checking
+/// the diagnostics it stops, so it doesn't keep the index from being updated.
+pub fn count_change(backend: &Backend) {
+    backend.changes.fetch_add(1, Ordering::SeqCst);
+}
+
+/// publishes the diagnostics of every file whose diagnostics changed since they were published last. Nothing is
+/// published if the diagnostics are superseded while they are checked.
+pub async fn publish(client: &Client, parsers: &RwLock<Parsers>, parse_errors: &RwLock<HashMap<String, Diagnostic>>, published_diagnostics: &tokio::sync::Mutex<HashMap<String, Vec<Diagnostic>>>, encoding: PositionEncoding, superseded: &(dyn Fn() -> bool + Sync)) {
+    // kept locked while publishing, so diagnostics of the background indexing and of an edit can't overtake each other
+    let mut published_diagnostics = published_diagnostics.lock().await;
+
+    let mut diagnostics = {
+        let parsers = parsers.read().unwrap();
+        let parse_errors = parse_errors.read().unwrap();
+
+        let mut diagnostics: HashMap<String, Vec<Diagnostic>> = parsers.keys().map(|file| (file.to_string(), Vec::new())).collect();
+
+        for (file, parse_error) in parse_errors.iter() {
+            diagnostics.entry(file.to_string()).or_default().push(parse_error.clone());
+        }
+
+        let index = WorkspaceIndex::new(&parsers);
+        check_references(&parsers, &index, encoding, superseded, &mut diagnostics);
+        duplicate_short_names(&parsers, &index, encoding, superseded, &mut diagnostics);
+        ecuc_multiplicities(&parsers, &index, encoding, superseded, &mut diagnostics);
+        ecuc_values(&parsers, &index, encoding, superseded, &mut diagnostics);
+        diagnostics
+    };
+    if superseded() {
+        return;
+    }
+
+    // files which are no longer parsed get their old diagnostics cleared
+    for file in published_diagnostics.keys() {
+        if !diagnostics.contains_key(file) {
+            diagnostics.insert(file.to_string(), Vec::new());
+        }
+    }
+
+    for (file, file_diagnostics) in diagnostics {
+        let unchanged = match published_diagnostics.get(&file) {
+            Some(published) => *published == file_diagnostics,
+            None => file_diagnostics.is_empty(),
+        };
+        if unchanged {
+            continue;
+        }
+
+        if let Ok(uri) = Url::from_file_path(&file) {
+            client.publish_diagnostics(uri, file_diagnostics.clone(), None).await;
+        }
+
+        if file_diagnostics.is_empty() {
+            published_diagnostics.remove(&file);
+        } else {
+            published_diagnostics.insert(file, file_diagnostics);
+        }
+    }
+}
+
+/// creates the diagnostic of a file which doesn't parse, the text the error was found in is needed to convert the
+/// position of the error into the encoding of the client
+pub fn parse_error_diagnostic(error: &Error, text: Option<&str>, encoding: PositionEncoding) -> Diagnostic {
+    // without a position the error is reported at the start of the file
+    let range = match error.downcast_ref::<roxmltree::Error>() {
+        Some(xml_error) => {
+            // roxmltree counts the characters of the line, without the text the column is used as it is
+            let pos = xml_error.pos();
+            let start = match text {
+                Some(text) => {
+                    let line_index = LineIndex::new(text);
+                    let line_start = line_index.offset(Position::new(pos.row-1, 0), encoding).unwrap_or(text.len()).min(text.len());
+                    let offset = text[line_start..].char_indices().nth(pos.col as usize - 1).map_or(text.len(), |(index, _)| line_start + index);
+                    line_index.position(offset, encoding)
+                },
+                None => Position::new(pos.row-1, pos.col-1),
+            };
+            Range {
+                start,
+                end: Position::new(pos.row, 0),
+            }
+        },
+        None => Range::default(),
+    };
+
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String("parse-error".to_string())),
+        source: Some(SOURCE.to_string()),
+        message: error.to_string(),
+        ..Default::default()
+    }
+}
+
+/// reports references which can't be resolved and references whose DEST doesn't match the referenced element
+fn check_references(parsers: &Parsers, index: &WorkspaceIndex, encoding: PositionEncoding, superseded: &(dyn Fn() -> bool + Sync), diagnostics: &mut HashMap<String, Vec<Diagnostic>>) {
+    for (file, parser) in parsers.iter() {
+        if superseded() {
+            return;
+        }
+        let file_diagnostics = diagnostics.get_mut(file).expect("diagnostics are initialized for every parser");
+
+        for (path, ref_nodes) in parser.refs.iter() {
+            let targets = index.resolve(path);
+
+            if targets.is_empty() {
+                for ref_node in ref_nodes {
+                    file_diagnostics.push(Diagnostic {
+                        range: parser.lsp_range(&ref_node.text_range, encoding),
+                        severity: Some(DiagnosticSeverity::WARNING),
+                        code: Some(NumberOrString::String("unresolved-reference".to_string())),
+                        source: Some(SOURCE.to_string()),
+                        message: if path.is_empty() { "Empty reference".to_string() } else { format!("Unresolved reference: {}", path) },
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+
+            let mut target_tags: Vec<&str> = targets.iter().map(|target| target.node.tag_name.as_str()).collect();
+            target_tags.sort();
+            target_tags.dedup();
+
+            for ref_node in ref_nodes {
+                if target_tags.contains(&ref_node.dest.as_str()) {
+                    continue;
+                }
+
+                file_diagnostics.push(Diagnostic {
+                    range: parser.lsp_range(&ref_node.dest_range, encoding),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    code: Some(NumberOrString::String("dest-mismatch".to_string())),
+                    source: Some(SOURCE.to_string()),
+                    message: format!("DEST {} does not match {} of the referenced element {}", ref_node.dest, target_tags.join(", "), path),
+                    // the actual tags are offered by the quick fix
+                    data: Some(serde_json::json!(target_tags)),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+fn duplicate_short_names(parsers: &Parsers, index: &WorkspaceIndex, encoding: PositionEncoding, superseded: &(dyn Fn() -> bool + Sync), diagnostics: &mut HashMap<String, Vec<Diagnostic>>) {
+    for (path, nodes) in index.declarations.iter() {
+        if superseded() {
+            return;
+        }
+        if nodes.len() < 2 || nodes.iter().all(|node| SPLITABLE_ELEMENTS.contains(&node.node.tag_name.as_str())) {
+            continue;
+        }
+
+        for node in nodes.iter() {
+            let related_information = nodes.iter()
+                .filter(|other| !std::ptr::eq(**other, *node))
+                .filter_map(|other| {
+                    Some(DiagnosticRelatedInformation {
+                        location: Location {
+                            uri: Url::from_file_path(&other.node.file).ok()?,
+                            range: parsers.get(other.node.file.as_str())?.lsp_range(&other.short_name_range, encoding),
+                        },
+                        message: format!("{} {} is also declared here", other.node.tag_name, path),
+                    })
+                })
+                .collect::<Vec<DiagnosticRelatedInformation>>();
+
+            if let (Some(file_diagnostics), Some(parser)) = (diagnostics.get_mut(node.node.file.as_str()), parsers.get(node.node.file.as_str())) {
+                file_diagnostics.push(Diagnostic {
+                    range: parser.lsp_range(&node.short_name_range, encoding),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    code: Some(NumberOrString::String("duplicate-short-name".to_string())),
+                    source: Some(SOURCE.to_string()),
+                    message: format!("Duplicate short-name path: {} is declared {} times", path, nodes.len()),
+                    related_information: Some(related_information),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+/// checks the number of sub-containers, parameters and references of every ECUC container against its definition. A
+/// container may be split across files, so the instances of all of its declarations are counted together.
+fn ecuc_multiplicities(parsers: &Parsers, index: &WorkspaceIndex, encoding: PositionEncoding, superseded: &(dyn Fn() -> bool + Sync), diagnostics: &mut HashMap<String, Vec<Diagnostic>>) {
+    for (path, declarations) in index.declarations.iter() {
+        if superseded() {
+            return;
+        }
+        let definition = match declarations.iter().find_map(|node| node.node.def_ref.as_ref()).and_then(|def_ref| index.definition(def_ref)) {
+            Some(definition) => definition,
+            None => continue,
+        };
+
+        // instances are matched to their definition by the last segment of the DEFINITION-REF, each one is reported
+        // in the file which declares it
+        let mut instances: HashMap<&str, Vec<(&str, Range)>> = HashMap::new();
+        // a split sub-container is a single instance
+        let mut sub_containers = index.children(path).to_vec();
+        sub_containers.sort_by_key(|node| (node.path.as_str(), node.node.file.as_str(), node.node.range.start));
+        sub_containers.dedup_by_key(|node| node.path.as_str());
+        for sub_container in sub_containers {
+            if let (Some(def_ref), Some(parser)) = (&sub_container.node.def_ref, parsers.get(sub_container.node.file.as_str())) {
+                let name = def_ref.rsplit('/').next().unwrap();
+                instances.entry(name).or_default().push((sub_container.node.file.as_str(), parser.lsp_range(&sub_container.short_name_range, encoding)));
+            }
+        }
+        for declaration in declarations.iter() {
+            if let Some(parser) = parsers.get(declaration.node.file.as_str()) {
+                for value in declaration.values.iter() {
+                    instances.entry(value.short_name.as_str()).or_default().push((declaration.node.file.as_str(), parser.lsp_range(&value.node.range, encoding)));
+                }
+            }
+        }
+        // the instances behind the upper multiplicity are reported, independent of the order of the files
+        for found in instances.values_mut() {
+            found.sort_by_key(|(file, range)| (*file, range.start.line, range.start.character));
+        }
+
+        // a missing element is reported once, at the first declaration
+        let first = declarations.iter().min_by_key(|node| (node.node.file.as_str(), node.node.range.start)).unwrap();
+        let first_range = match parsers.get(first.node.file.as_str()) {
+            Some(parser) => parser.lsp_range(&first.short_name_range, encoding),
+            None => continue,
+        };
+
+        // the sub-containers of a choice container are alternatives, none of them is mandatory
+        let is_choice = definition.node.tag_name == "ECUC-CHOICE-CONTAINER-DEF";
+
+        for child_definition in index.children(&definition.path) {
+            let multiplicity = match &child_definition.multiplicity {
+                Some(multiplicity) => multiplicity,
+                None => continue,
+            };
+            let found = instances.get(child_definition.short_name.as_str()).map_or(&[] as &[(&str, Range)], |found| found.as_slice());
+            let kind = definition_kind(&child_definition.node.tag_name);
+
+            if !is_choice && (found.len() as u32) < multiplicity.lower {
+                if let Some(file_diagnostics) = diagnostics.get_mut(first.node.file.as_str()) {
+                    file_diagnostics.push(Diagnostic {
+                        range: first_range,
+                        severity: Some(DiagnosticSeverity::WARNING),
+                        code: Some(NumberOrString::String("missing-ecuc-element".to_string())),
+                        source: Some(SOURCE.to_string()),
+                        message: format!("Missing mandatory {} {}: expected at least {}, found {}", kind, child_definition.path, multiplicity.lower, found.len()),
+                        ..Default::default()
+                    });
+                }
+            }
+
+            if let Some(upper) = multiplicity.upper {
+                for (file, range) in found.iter().skip(upper as usize) {
+                    if let Some(file_diagnostics) = diagnostics.get_mut(*file) {
+                        file_diagnostics.push(Diagnostic {
+                            range: *range,
+                            severity: Some(DiagnosticSeverity::ERROR),
+                            code: Some(NumberOrString::String("too-many-ecuc-elements".to_string())),
+                            source: Some(SOURCE.to_string()),
+                            message: format!("Too many instances of {} {}: expected at most {}, found {}", kind, child_definition.path, upper, found.len()),
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn definition_kind(tag_name: &str) -> &'static str {
+    if tag_name.ends_with("CONTAINER-DEF") {
+        "container"
+    } else if tag_name.ends_with("REFERENCE-DEF") {
+        "reference"
+    } else {
+        "parameter"
+    }
+}
+
+/// checks the values of ECUC parameters against the constraints of their definition
+fn ecuc_values(parsers: &Parsers, index: &WorkspaceIndex, encoding: PositionEncoding, superseded: &(dyn Fn() -> bool + Sync), diagnostics: &mut HashMap<String, Vec<Diagnostic>>) {
+    // regular expressions are shared by all values of a definition, so they are only compiled once
+    let mut regexes: HashMap<String, Option<Regex>> = HashMap::new();
+
+    for (file, parser) in parsers.iter() {
+        if superseded() {
+            return;
+        }
+        let file_diagnostics = diagnostics.get_mut(file).expect("diagnostics are initialized for every parser");
+
+        for value in parser.ident_nodes.values().flat_map(|node| node.values.iter()) {
+            if value.value.is_empty() || (value.node.tag_name != "ECUC-NUMERICAL-PARAM-VALUE" && value.node.tag_name != "ECUC-TEXTUAL-PARAM-VALUE") {
+                continue;
+            }
+
+            let definition = match value.node.def_ref.as_ref().and_then(|def_ref| index.definition(def_ref)) {
+                Some(definition) => definition,
+                None => continue,
+            };
+
+            if let Some((code, message)) = check_value(value, definition, index, &mut regexes) {
+                file_diagnostics.push(Diagnostic {
+                    range: parser.lsp_range(&value.value_range, encoding),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    code: Some(NumberOrString::String(code.to_string())),
+                    source: Some(SOURCE.to_string()),
+                    message,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+/// returns the diagnostic code and message if the value violates its definition
+fn check_value(value: &ValueNode, definition: &IdentNode, index: &WorkspaceIndex, regexes: &mut HashMap<String, Option<Regex>>) -> Option<(&'static str, String)> {
+    let text = value.value.trim();
+    let path = &definition.path;
+    let param_def = definition.param_def.as_ref()?;
+
+    match definition.node.tag_name.as_str() {
+        "ECUC-INTEGER-PARAM-DEF" => {
+            let number = match parse_integer(text) {
+                Some(number) => number,
+                None => return Some(("invalid-value", format!("value {} is not a valid integer for {}", text, path))),
+            };
+            if let Some(min) = param_def.min.as_ref().filter(|min| parse_integer(min).is_some_and(|min| number < min)) {
+                return Some(("value-out-of-range", format!("value {} is below MIN {} of {}", text, min, path)));
+            }
+            if let Some(max) = param_def.max.as_ref().filter(|max| parse_integer(max).is_some_and(|max| number > max)) {
+                return Some(("value-out-of-range", format!("value {} exceeds MAX {} of {}", text, max, path)));
+            }
+        },
+        "ECUC-FLOAT-PARAM-DEF" => {
+            let number = match text.parse::<f64>() {
+                Ok(number) => number,
+                Err(_) => return Some(("invalid-value", format!("value {} is not a valid float for {}", text, path))),
+            };
+            if let Some(min) = param_def.min.as_ref().filter(|min| min.parse::<f64>().is_ok_and(|min| number < min)) {
+                return Some(("value-out-of-range", format!("value {} is below MIN {} of {}", text, min, path)));
+            }
+            if let Some(max) = param_def.max.as_ref().filter(|max| max.parse::<f64>().is_ok_and(|max| number > max)) {
+                return Some(("value-out-of-range", format!("value {} exceeds MAX {} of {}", text, max, path)));
+            }
+        },
+        "ECUC-BOOLEAN-PARAM-DEF" if !["true", "false", "1", "0"].contains(&text) => {
+            return Some(("invalid-value", format!("value {} is not a valid boolean (true, false, 1, 0) for {}", text, path)));
+        },
+        "ECUC-ENUMERATION-PARAM-DEF" => {
+            let literals: Vec<&str> = index.children(path).iter()
+                .filter(|child| child.node.tag_name == "ECUC-ENUMERATION-LITERAL-DEF")
+                .map(|child| child.short_name.as_str())
+                .collect();
+            // a definition without literals, e.g. one refined by a vendor, accepts any value
+            if !literals.is_empty() && !literals.contains(&text) {
+                return Some(("invalid-enum-literal", format!("value {} is not a literal of {} (expected one of {})", text, path, literals.join(", "))));
+            }
+        },
+        "ECUC-STRING-PARAM-DEF" | "ECUC-FUNCTION-NAME-DEF" | "ECUC-LINKER-SYMBOL-DEF" | "ECUC-MULTILINE-STRING-PARAM-DEF" => {
+            let length = text.chars().count();
+            if let Some(min_length) = param_def.min_length.filter(|min_length| length < *min_length) {
+                return Some(("value-out-of-range", format!("value length {} is below MIN-LENGTH {} of {}", length, min_length, path)));
+            }
+            if let Some(max_length) = param_def.max_length.filter(|max_length| length > *max_length) {
+                return Some(("value-out-of-range", format!("value length {} exceeds MAX-LENGTH {} of {}", length, max_length, path)));
+            }
+            if let Some(regular_expression) = &param_def.regular_expression {
+                // the whole value has to match, an invalid expression in the definition is ignored
+                let regex = regexes.entry(regular_expression.to_string())
+                    .or_insert_with(|| Regex::new(&format!("^(?:{})$", regular_expression)).ok());
+                if regex.as_ref().is_some_and(|regex| !regex.is_match(text)) {
+                    return Some(("invalid-value", format!("value {} does not match REGULAR-EXPRESSION {} of {}", text, regular_expression, path)));
+                }
+            }
+        },
+        _ => (),
+    }
+
+    None
+}
+
+/// parses decimal, hexadecimal (0x) and binary (0b) integers
+fn parse_integer(text: &str) -> Option<i128> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let number = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i128::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i128::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse::<i128>().ok()?
+    };
+
+    Some(if negative { -number } else { number })
+}
@@ -12,9 +12,10 @@ pub async fn document_symbol(backend: &Backend, params: DocumentSymbolParams) ->
     let file_path = params.text_document.uri.to_file_path().unwrap();
     let file_name = file_path.to_str().unwrap();
 
-    if let Some(parser) = backend.parsers.get(file_name) {
+    let mut root_symbols = HashMap::new();
 
-        let mut root_symbols = HashMap::new();
+    // the index isn't held while talking to the client
+    if let Some(parser) = backend.parsers.read().unwrap().get(file_name) {
 
         parser.ident_nodes.values().for_each(|node| {
             let parent_path = node.path.rsplit_once('/').unwrap().0;
@@ -46,14 +47,8 @@ pub async fn document_symbol(backend: &Backend, params: DocumentSymbolParams) ->
                 detail: detail,
                 kind: typ,
                 deprecated: None,
-                range: Range {
-                    start: Position::new(node.node.start.row-1, node.node.start.col-1),
-                    end: Position::new(node.node.end.row-1, node.node.end.col-1),
-                },
-                selection_range: Range {
-                    start: Position::new(node.short_name_start.row-1, node.short_name_start.col-1),
-                    end: Position::new(node.short_name_end.row-1, node.short_name_end.col-1),
-                },
+                range: parser.lsp_range(&node.node.range, backend.position_encoding),
+                selection_range: parser.lsp_range(&node.short_name_range, backend.position_encoding),
                 children: Some(Vec::new()),
                 tags: None,
             };
@@ -62,18 +57,12 @@ pub async fn document_symbol(backend: &Backend, params: DocumentSymbolParams) ->
             node.values.iter().for_each(|value| {
                 let detail = Some("= ".to_string() + &value.value);
                 let value_symbol = DocumentSymbol {
-                    name: value.short_name.clone(),
+                    name: value.short_name.to_string(),
                     detail: detail,
                     kind: value.typ,
                     deprecated: None,
-                    range: Range {
-                        start: Position::new(value.node.start.row-1, value.node.start.col-1),
-                        end: Position::new(value.node.end.row-1, value.node.end.col-1),
-                    },
-                    selection_range: Range {
-                        start: Position::new(value.node.start.row-1, value.node.start.col-1),
-                        end: Position::new(value.node.end.row-1, value.node.end.col-1),
-                    },
+                    range: parser.lsp_range(&value.node.range, backend.position_encoding),
+                    selection_range: parser.lsp_range(&value.node.range, backend.position_encoding),
                     children: None,
                     tags: None,
                 };
@@ -94,12 +83,13 @@ pub async fn document_symbol(backend: &Backend, params: DocumentSymbolParams) ->
                 
             }
         });
-        backend.client
-            .log_message(MessageType::INFO, "document symbols request done!")
-            .await;
-
-        Ok(Some(DocumentSymbolResponse::Nested(root_symbols.into_values().collect())))
     } else {
-        Ok(None)
+        return Ok(None);
     }
+
+    backend.client
+        .log_message(MessageType::INFO, "document symbols request done!")
+        .await;
+
+    Ok(Some(DocumentSymbolResponse::Nested(root_symbols.into_values().collect())))
 }
\ No newline at end of file
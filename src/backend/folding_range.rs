@@ -0,0 +1,44 @@
+use tower_lsp::lsp_types::*;
+use tower_lsp::jsonrpc::Result;
+
+use crate::xml_parser::{PositionEncoding, XmlParser};
+
+use super::Backend;
+
+pub async fn folding_range(backend: &Backend, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+    let file_path = params.text_document.uri.to_file_path().unwrap();
+    let file_name = file_path.to_str().unwrap();
+
+    let parsers = backend.parsers.read().unwrap();
+    Ok(parsers.get(file_name).map(|parser| folding_ranges(parser, backend.position_encoding)))
+}
+
+/// folds the identifiable elements, which includes the packages and containers, and the blocks of additional
+/// information. The values aren't folded, a container would mostly consist of folded values otherwise.
+fn folding_ranges(parser: &XmlParser, encoding: PositionEncoding) -> Vec<FoldingRange> {
+    let elements = parser.ident_nodes.values().chain(parser.duplicate_nodes.iter())
+        .map(|node| (&node.node.range, None));
+    let blocks = parser.blocks.iter()
+        .map(|block| (block, Some(FoldingRangeKind::Region)));
+
+    let mut folding_ranges: Vec<FoldingRange> = elements.chain(blocks)
+        .filter_map(|(range, kind)| {
+            let range = parser.lsp_range(range, encoding);
+            // the line of the end tag stays visible, like in the folding of other markup
+            if range.end.line <= range.start.line + 1 {
+                return None;
+            }
+            Some(FoldingRange {
+                start_line: range.start.line,
+                end_line: range.end.line - 1,
+                kind,
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    folding_ranges.sort_by_key(|folding_range| (folding_range.start_line, std::cmp::Reverse(folding_range.end_line)));
+    // clients fold a single range per line, the outermost one is kept
+    folding_ranges.dedup_by_key(|folding_range| folding_range.start_line);
+    folding_ranges
+}
@@ -0,0 +1,39 @@
+use tower_lsp::lsp_types::*;
+use tower_lsp::lsp_types::request::{GotoTypeDefinitionParams, GotoTypeDefinitionResponse};
+use tower_lsp::jsonrpc::Result;
+
+use super::{find_ident_nodes, Backend};
+
+/// goes from a value, or from anywhere inside of a container, to the definition named by its DEFINITION-REF
+pub async fn goto_type_definition(backend: &Backend, params: GotoTypeDefinitionParams) -> Result<Option<GotoTypeDefinitionResponse>> {
+    let file_path = params.text_document_position_params.text_document.uri.to_file_path().expect("Failed to convert URI to path");
+    let file_name = file_path.to_str().expect("Failed to convert path to string");
+    let position = params.text_document_position_params.position;
+    let parsers = backend.parsers.read().unwrap();
+    let parser = match parsers.get(file_name) {
+        Some(parser) => parser,
+        None => return Ok(None),
+    };
+
+    // a value is inside of its container, so it is looked at first
+    let def_ref = match parser.get_value_element_at(position, backend.position_encoding) {
+        Some(value) => value.node.def_ref.as_ref(),
+        None => parser.get_ident_node_at(position, backend.position_encoding).and_then(|node| node.node.def_ref.as_ref()),
+    };
+    let def_ref = match def_ref {
+        Some(def_ref) => def_ref,
+        None => return Ok(None),
+    };
+
+    let links = find_ident_nodes(&parsers, def_ref).into_iter().map(|node| {
+        let target_parser = &parsers[node.node.file.as_str()];
+        LocationLink {
+            origin_selection_range: None,
+            target_uri: Url::from_file_path(node.node.file.as_str()).unwrap(),
+            target_range: target_parser.lsp_range(&node.node.range, backend.position_encoding),
+            target_selection_range: target_parser.lsp_range(&node.short_name_range, backend.position_encoding),
+        }
+    }).collect();
+
+    Ok(Some(GotoTypeDefinitionResponse::Link(links)))
+}
@@ -8,21 +8,22 @@ pub async fn hover(backend: &Backend, params: HoverParams) -> Result<Option<Hove
     let file_path = params.text_document_position_params.text_document.uri.to_file_path().unwrap();
     let file_name = file_path.to_str().unwrap();
     let mut hover = None;
-    if let Some(parser) = backend.parsers.get(file_name) {
+    let parsers = backend.parsers.read().unwrap();
+    if let Some(parser) = parsers.get(file_name) {
 
-        let node = parser.get_ident_node_at(params.text_document_position_params.position.line as usize, params.text_document_position_params.position.character as usize);
+        let node = parser.get_ident_node_at(params.text_document_position_params.position, backend.position_encoding);
         if node.is_some() {
             let node = node.unwrap();
 
             eprintln!("got node: {:?}", node.node.file);
 
             let mut refs = "".to_owned();
-            for (_, parser) in backend.parsers.iter() {
+            for (_, parser) in parsers.iter() {
                 // eprintln!("parser: {:?}", parser.file);
                 let mut nodes = Vec::new();
                 nodes.push(parser.refs.get(&node.path));
                 if node.path.starts_with("/MICROSAR") {
-                    nodes.push(parser.refs.get(&node.path.replace("/MICROSAR", "/AUTOSAR/EcucDefs")));
+                    nodes.push(parser.refs.get(node.path.replace("/MICROSAR", "/AUTOSAR/EcucDefs").as_str()));
                 }
                 for ref_node in nodes {
                     if ref_node.is_some() {
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use rayon::prelude::*;
+use tokio::time::Instant;
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::*;
+use tower_lsp::Client;
+
+use crate::index_cache::IndexCache;
+use crate::xml_parser::{PositionEncoding, XmlParser};
+
+use super::diagnostics::publish;
+use super::{Backend, Parsers};
+
+/// number of files which are parsed before they are added to the index
+const BATCH_SIZE: usize = 32;
+
+static INDEXING_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// parses the files in the background, cancelling a running indexing. Every batch is added to the index as soon
+/// as it is parsed, so requests are served for these files while the rest is still being parsed. Files which were
+/// parsed in the meantime (e.g. because they were opened) are left alone.
+pub fn index_files(backend: &mut Backend, files: Vec<PathBuf>) {
+    backend.cancel_indexing();
+    if files.is_empty() {
+        return;
+    }
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    backend.indexing = Some(cancelled.clone());
+    spawn_indexing(backend, files, cancelled, None);
+}
+
+/// parses files which changed on disk again in the background, without cancelling a running indexing. Their parsers
+/// are replaced once the new ones are ready, so the files stay in the index meanwhile. The index of a document which
+/// is kept in sync by the client belongs to its content and is left alone.
+pub fn reindex_files(backend: &Backend, files: Vec<PathBuf>) {
+    if files.is_empty() {
+        return;
+    }
+
+    spawn_indexing(backend, files, Arc::new(AtomicBool::new(false)), Some(backend.synced_documents.clone()));
+}
+
+/// adds the parsers to the index batch by batch and publishes the diagnostics once all files are parsed. If the synced
+/// documents are given, the parsers of the other files are replaced, otherwise only new files are added.
+fn spawn_indexing(backend: &Backend, files: Vec<PathBuf>, cancelled: Arc<AtomicBool>, synced_documents: Option<Arc<RwLock<HashSet<String>>>>) {
+    let client = backend.client.clone();
+    let parsers = backend.parsers.clone();
+    let parse_errors = backend.parse_errors.clone();
+    let published_diagnostics = backend.published_diagnostics.clone();
+    let work_done_progress = backend.work_done_progress;
+    let semantic_tokens_refresh = backend.semantic_tokens_refresh;
+    let code_lens_refresh = backend.code_lens_refresh;
+    let inlay_hint_refresh = backend.inlay_hint_refresh;
+    let index_cache = backend.index_cache.clone();
+    let position_encoding = backend.position_encoding;
+
+    tokio::spawn(async move {
+        let now = Instant::now();
+        let token = NumberOrString::String(format!("arxml-indexing-{}", INDEXING_RUN.fetch_add(1, Ordering::SeqCst)));
+        let progress = work_done_progress && client.send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams { token: token.clone() }).await.is_ok();
+
+        if progress {
+            report(&client, &token, WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: "Indexing ARXML files".to_string(),
+                cancellable: Some(false),
+                message: Some(format!("0/{}", files.len())),
+                percentage: Some(0),
+            })).await;
+        }
+
+        let mut parsed = 0;
+        for batch in files.chunks(BATCH_SIZE) {
+            if cancelled.load(Ordering::SeqCst) {
+                break;
+            }
+            if progress {
+                let file_name = batch[0].file_name().and_then(|name| name.to_str()).unwrap_or_default();
+                report(&client, &token, WorkDoneProgress::Report(WorkDoneProgressReport {
+                    cancellable: Some(false),
+                    message: Some(format!("{}/{} {}", parsed, files.len(), file_name)),
+                    percentage: Some((parsed * 100 / files.len()) as u32),
+                })).await;
+            }
+
+            let batch = batch.to_vec();
+            let index_cache = index_cache.clone();
+            let results = match tokio::task::spawn_blocking(move || parse_batch(&batch, index_cache.as_deref(), position_encoding)).await {
+                Ok(results) => results,
+                Err(_) => break,
+            };
+            add_batch(results, &parsers, &parse_errors, &cancelled, synced_documents.as_deref());
+            parsed += BATCH_SIZE.min(files.len() - parsed);
+        }
+
+        let finished = !cancelled.load(Ordering::SeqCst);
+        if progress {
+            report(&client, &token, WorkDoneProgress::End(WorkDoneProgressEnd {
+                message: Some(if finished { format!("{} files", files.len()) } else { "cancelled".to_string() }),
+            })).await;
+        }
+
+        if finished {
+            eprintln!("indexing {} files took: {:?}", files.len(), now.elapsed());
+            publish(&client, &parsers, &parse_errors, &published_diagnostics, position_encoding, &|| false).await;
+            // references of the open files may have been resolved by the new files
+            if semantic_tokens_refresh {
+                let _ = client.semantic_tokens_refresh().await;
+            }
+            if code_lens_refresh {
+                let _ = client.code_lens_refresh().await;
+            }
+            if inlay_hint_refresh {
+                let _ = client.inlay_hint_refresh().await;
+            }
+        }
+    });
+}
+
+async fn report(client: &Client, token: &NumberOrString, progress: WorkDoneProgress) {
+    client.send_notification::<Progress>(ProgressParams {
+        token: token.clone(),
+        value: ProgressParamsValue::WorkDone(progress),
+    }).await;
+}
+
+fn parse_batch(files: &[PathBuf], index_cache: Option<&IndexCache>, encoding: PositionEncoding) -> Vec<(String, Result<XmlParser, Diagnostic>)> {
+    files.par_iter().map(|file| {
+        let result = Backend::create_parser_sync(file, true, None, index_cache).map_err(|e| Backend::parse_error(file, None, &e, encoding));
+        (file.to_str().unwrap().to_string(), result)
+    }).collect()
+}
+
+/// adds the parsed files to the index, unless the indexing was cancelled or they were parsed in the meantime. Files are
+/// replaced if the synced documents are given, except for these documents.
+fn add_batch(results: Vec<(String, Result<XmlParser, Diagnostic>)>, parsers: &RwLock<Parsers>, parse_errors: &RwLock<HashMap<String, Diagnostic>>, cancelled: &AtomicBool, synced_documents: Option<&RwLock<HashSet<String>>>) {
+    let mut parsers = parsers.write().unwrap();
+    let mut parse_errors = parse_errors.write().unwrap();
+    // checked while locked, so nothing is added after the files were reconciled
+    if cancelled.load(Ordering::SeqCst) {
+        return;
+    }
+    let synced_documents = synced_documents.map(|synced_documents| synced_documents.read().unwrap());
+    for (file_name, result) in results {
+        let skip = match &synced_documents {
+            Some(synced_documents) => synced_documents.contains(&file_name),
+            None => parsers.contains_key(&file_name) || parse_errors.contains_key(&file_name),
+        };
+        if skip {
+            continue;
+        }
+        // like for an edit, the last good parser of a file which doesn't parse is kept
+        match result {
+            Ok(parser) => {
+                parse_errors.remove(&file_name);
+                parsers.insert(file_name, parser);
+            },
+            Err(diagnostic) => { parse_errors.insert(file_name, diagnostic); },
+        }
+    }
+}
@@ -1,6 +1,13 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use tower_lsp::lsp_types::*;
 use tower_lsp::jsonrpc::Result;
 
+use crate::index_cache::IndexCache;
+use crate::xml_parser::PositionEncoding;
+
+use super::semantic_tokens::legend;
 use super::Backend;
 
 pub fn init(backend: &mut Backend, params: InitializeParams) -> Result<InitializeResult> {
@@ -11,12 +18,48 @@ pub fn init(backend: &mut Backend, params: InitializeParams) -> Result<Initializ
         if let Some(instant_symbol_refresh) = init_options.get("instantSymbolRefresh") {
             if let Some(value) = instant_symbol_refresh.as_bool() {
                 if value {
-                    sync_type = TextDocumentSyncKind::FULL;
+                    sync_type = TextDocumentSyncKind::INCREMENTAL;
                 }
             }
         }
     }
 
+    backend.text_sync = sync_type;
+
+    // an empty cache directory disables the index cache
+    let cache_dir = match params.initialization_options.as_ref().and_then(|init_options| init_options.get("indexCacheDirectory")) {
+        Some(cache_dir) => cache_dir.as_str().filter(|cache_dir| !cache_dir.is_empty()).map(PathBuf::from),
+        None => Some(IndexCache::default_dir()),
+    };
+    backend.index_cache = cache_dir.map(|cache_dir| Arc::new(IndexCache::new(cache_dir)));
+
+    backend.dynamic_file_watching = params.capabilities.workspace.as_ref()
+        .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+        .and_then(|watched_files| watched_files.dynamic_registration)
+        .unwrap_or(false);
+
+    backend.semantic_tokens_refresh = params.capabilities.workspace.as_ref()
+        .and_then(|workspace| workspace.semantic_tokens.as_ref())
+        .and_then(|semantic_tokens| semantic_tokens.refresh_support)
+        .unwrap_or(false);
+
+    backend.code_lens_refresh = params.capabilities.workspace.as_ref()
+        .and_then(|workspace| workspace.code_lens.as_ref())
+        .and_then(|code_lens| code_lens.refresh_support)
+        .unwrap_or(false);
+
+    backend.inlay_hint_refresh = params.capabilities.workspace.as_ref()
+        .and_then(|workspace| workspace.inlay_hint.as_ref())
+        .and_then(|inlay_hint| inlay_hint.refresh_support)
+        .unwrap_or(false);
+
+    backend.work_done_progress = params.capabilities.window.as_ref()
+        .and_then(|window| window.work_done_progress)
+        .unwrap_or(false);
+
+    backend.position_encoding = PositionEncoding::negotiate(params.capabilities.general.as_ref()
+        .and_then(|general| general.position_encodings.as_deref()));
+
     if let Some(ws_folders) = params.workspace_folders {
         backend.ws_folder = ws_folders;
     }
@@ -24,14 +67,42 @@ pub fn init(backend: &mut Backend, params: InitializeParams) -> Result<Initializ
     Ok(InitializeResult {
         server_info: None,
         capabilities: ServerCapabilities { 
+            position_encoding: Some(backend.position_encoding.kind()),
             definition_provider: Some(OneOf::Left(true)),
+            type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
             references_provider: Some(OneOf::Left(true)),
             hover_provider: Some(HoverProviderCapability::Simple(true)),
+            // the edits of a rename are computed from the index, which only has the contents of open documents if
+            // they are synced
+            rename_provider: (sync_type != TextDocumentSyncKind::NONE).then_some(OneOf::Right(RenameOptions {
+                prepare_provider: Some(true),
+                work_done_progress_options: Default::default(),
+            })),
+            completion_provider: Some(CompletionOptions {
+                trigger_characters: Some(vec!["/".to_string()]),
+                ..Default::default()
+            }),
+            code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                work_done_progress_options: Default::default(),
+                resolve_provider: None,
+            })),
             workspace: Some(WorkspaceServerCapabilities {
                 workspace_folders:Some(WorkspaceFoldersServerCapabilities{
                     supported:Some(true),
                     change_notifications:Some(OneOf::Left(true)),}),
                     file_operations: None }),
+            semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                legend: legend(),
+                range: Some(true),
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                work_done_progress_options: Default::default(),
+            })),
+            code_lens_provider: Some(CodeLensOptions {
+                resolve_provider: Some(true),
+            }),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
             document_symbol_provider: Some(OneOf::Left(true)),
             workspace_symbol_provider: Some(OneOf::Left(true)),
             text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
@@ -0,0 +1,101 @@
+use tower_lsp::lsp_types::*;
+use tower_lsp::jsonrpc::Result;
+
+use crate::xml_parser::{IdentNode, ValueNode};
+
+use super::{find_ident_nodes, Backend};
+
+pub async fn inlay_hint(backend: &Backend, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+    let file_path = params.text_document.uri.to_file_path().unwrap();
+    let file_name = file_path.to_str().unwrap();
+
+    let parsers = backend.parsers.read().unwrap();
+    let parser = match parsers.get(file_name) {
+        Some(parser) => parser,
+        None => return Ok(None),
+    };
+
+    let mut hints = Vec::new();
+    for value in parser.ident_nodes.values().chain(parser.duplicate_nodes.iter()).flat_map(|node| node.values.iter()) {
+        let position = parser.lsp_range(&value.value_range, backend.position_encoding).end;
+        if position < params.range.start || params.range.end < position {
+            continue;
+        }
+
+        let def_ref = match value.node.def_ref.as_deref() {
+            Some(def_ref) => def_ref,
+            None => continue,
+        };
+        // the first definition is used, like for the completion of enumeration literals
+        let definition = match find_ident_nodes(&parsers, def_ref).into_iter().find(|definition| definition.param_def.is_some()) {
+            Some(definition) => definition,
+            None => continue,
+        };
+
+        if let Some(label) = hint_label(value, definition) {
+            hints.push(InlayHint {
+                position,
+                label: InlayHintLabel::String(label),
+                kind: None,
+                text_edits: None,
+                tooltip: Some(InlayHintTooltip::String(match &definition.desc {
+                    Some(desc) => format!("{}\n\n{}", definition.path, desc),
+                    None => definition.path.to_string(),
+                })),
+                padding_left: Some(true),
+                padding_right: None,
+                data: None,
+            });
+        }
+    }
+
+    Ok(Some(hints))
+}
+
+/// describes the default value, the constraints and the unit of the definition, `None` if it has none of them
+fn hint_label(value: &ValueNode, definition: &IdentNode) -> Option<String> {
+    let param_def = definition.param_def.as_ref()?;
+    let default_value = param_def.default_value.as_deref();
+    let mut parts = Vec::new();
+
+    match definition.node.tag_name.as_str() {
+        "ECUC-INTEGER-PARAM-DEF" | "ECUC-FLOAT-PARAM-DEF" => {
+            parts.extend(default_value.map(|default_value| format!("default {}", default_value)));
+            parts.extend(bounds(param_def.min.as_deref(), param_def.max.as_deref()));
+            // the short name of the unit is shown, its path is of no interest next to a value
+            parts.extend(param_def.unit.as_deref().and_then(|unit| unit.rsplit('/').next()).map(|unit| format!("unit {}", unit)));
+        },
+        "ECUC-ENUMERATION-PARAM-DEF" => {
+            // the literal is already shown, so only whether it is the default is of interest
+            match default_value {
+                Some(default_value) if default_value == value.value.trim() => parts.push("default".to_string()),
+                Some(default_value) => parts.push(format!("default {}", default_value)),
+                None => (),
+            }
+        },
+        "ECUC-STRING-PARAM-DEF" | "ECUC-FUNCTION-NAME-DEF" | "ECUC-LINKER-SYMBOL-DEF" | "ECUC-MULTILINE-STRING-PARAM-DEF" => {
+            parts.extend(default_value.map(|default_value| format!("default {}", default_value)));
+            let (min_length, max_length) = (param_def.min_length.map(|min| min.to_string()), param_def.max_length.map(|max| max.to_string()));
+            parts.extend(bounds(min_length.as_deref(), max_length.as_deref()).map(|bounds| format!("length {}", bounds)));
+        },
+        _ => {
+            parts.extend(default_value.map(|default_value| format!("default {}", default_value)));
+        },
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(", "))
+    }
+}
+
+/// formats the bounds of a range as written in the definition, e.g. `0..255` or `>= 0`
+fn bounds(min: Option<&str>, max: Option<&str>) -> Option<String> {
+    match (min, max) {
+        (Some(min), Some(max)) => Some(format!("{}..{}", min, max)),
+        (Some(min), None) => Some(format!(">= {}", min)),
+        (None, Some(max)) => Some(format!("<= {}", max)),
+        (None, None) => None,
+    }
+}
@@ -1,10 +1,12 @@
-use regex::RegexSet;
+use std::collections::HashMap;
+
 use tokio::time::Instant;
 use tower_lsp::lsp_types::*;
+use tower_lsp::lsp_types::request::{GotoTypeDefinitionParams, GotoTypeDefinitionResponse};
 use tower_lsp::LanguageServer;
 use tower_lsp::jsonrpc::Result;
 
-use crate::backend::ClientConfig;
+use crate::text_document::TextDocument;
 
 use super::Backend;
 
@@ -17,18 +19,24 @@ impl LanguageServer for Backend {
     async fn initialized(&mut self, _: InitializedParams) {
         self.client.log_message(MessageType::INFO, "initialized!").await;
         
-        let result = self.client.configuration(vec![ConfigurationItem{scope_uri: None, section: Some("arxmlLanguageServer.ignorePattern".to_string()) }]).await;
-
-        let regexs = RegexSet::new(result.unwrap()[0].as_array().unwrap().iter().map(|x| x.as_str().unwrap())).unwrap();
-
-        self.config = Some(ClientConfig {
-            ignore_regex_set: regexs,
-        });
+        self.update_config().await;
 
-        let now = Instant::now();
+        // the diagnostics are published once the indexing is done
         self.parse_ws();
-        let elapsed = now.elapsed();
-        eprintln!("parsing took: {:?}", elapsed);
+
+        if self.dynamic_file_watching {
+            let options = DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![FileSystemWatcher { glob_pattern: GlobPattern::String("**/*.arxml".to_string()), kind: None }],
+            };
+            let registration = Registration {
+                id: "arxml-file-watcher".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: serde_json::to_value(options).ok(),
+            };
+            if let Err(e) = self.client.register_capability(vec![registration]).await {
+                self.client.log_message(MessageType::ERROR, format!("could not register file watcher: {:?}", e)).await;
+            }
+        }
     }
 
     async fn shutdown(&mut self) -> Result<()> {
@@ -39,25 +47,65 @@ impl LanguageServer for Backend {
         Ok(())
     }
 
-    async fn did_change_workspace_folders(&mut self, _: DidChangeWorkspaceFoldersParams) {
+    async fn did_change_workspace_folders(&mut self, params: DidChangeWorkspaceFoldersParams) {
         self.client
             .log_message(MessageType::INFO, "workspace folders changed!")
             .await;
+
+        let event = params.event;
+        self.ws_folder.retain(|folder| !event.removed.iter().any(|removed| removed.uri == folder.uri));
+        self.ws_folder.extend(event.added);
+
+        let now = Instant::now();
+        self.update_ws_files();
+        eprintln!("updating workspace files took: {:?}", now.elapsed());
+
+        super::publish_diagnostics(self).await;
     }
 
     async fn did_change_configuration(&mut self, _: DidChangeConfigurationParams) {
         self.client
             .log_message(MessageType::INFO, "configuration changed!")
             .await;
+
+        // the settings sent with the notification differ between clients, so they are pulled again
+        self.update_config().await;
+
+        let now = Instant::now();
+        self.update_ws_files();
+        eprintln!("updating workspace files took: {:?}", now.elapsed());
+
+        super::publish_diagnostics(self).await;
     }
 
     async fn did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams) {
         self.client.log_message(MessageType::INFO, "watched files have changed!").await;
-        params.changes.iter().for_each(|change| {
-            if change.typ == FileChangeType::DELETED {
-                self.parsers.remove(change.uri.to_file_path().unwrap().to_str().unwrap());
+
+        // a burst of changes (e.g. from a checkout) is reduced to the last change of each file
+        let mut changes = HashMap::new();
+        params.changes.into_iter().for_each(|change| {
+            if let Ok(file_path) = change.uri.to_file_path() {
+                changes.insert(file_path, change.typ);
             }
         });
+
+        let mut files = Vec::new();
+        for (file_path, typ) in changes {
+            let file_name = file_path.to_str().unwrap();
+            if typ == FileChangeType::DELETED {
+                self.parsers.write().unwrap().remove(file_name);
+                self.parse_errors.write().unwrap().remove(file_name);
+            } else if self.is_ws_file(&file_path) {
+                files.push(file_path);
+            }
+        }
+
+        if files.is_empty() {
+            super::publish_diagnostics(self).await;
+        } else {
+            // the diagnostics are published once the files are parsed
+            super::reindex_files(self, files);
+        }
     }
 
     async fn did_open(&mut self, params: DidOpenTextDocumentParams) {
@@ -66,8 +114,15 @@ impl LanguageServer for Backend {
         let file_path = params.text_document.uri.to_file_path().unwrap();
         let file_name = file_path.to_str().unwrap();
 
-        if !self.parsers.contains_key(file_name) {
+        if self.text_sync != TextDocumentSyncKind::NONE {
+            // the buffer may differ from the file, the index has to match it to be updated by the changes
+            self.synced_documents.write().unwrap().insert(file_name.to_string());
+            let _ = self.create_parser(&file_path, Some(&params.text_document.text)).await;
+            self.documents.insert(file_name.to_string(), TextDocument::new(params.text_document.text));
+            super::publish_diagnostics(self).await;
+        } else if !self.parsers.read().unwrap().contains_key(file_name) {
             let _ = self.create_parser(&file_path, None).await;
+            super::publish_diagnostics(self).await;
         }
     }
 
@@ -78,28 +133,49 @@ impl LanguageServer for Backend {
         
         let file_path = params.text_document.uri.to_file_path().unwrap();
         let file_name = file_path.to_str().unwrap();
-        if self.parsers.contains_key(file_name) {
-            self.parsers.remove(file_name);
-        }
 
         // let mut f = File::create("foo.txt").unwrap();
         // f.write_all(params.content_changes.get(0).unwrap().text.as_bytes()).unwrap();
 
-        let result = Backend::create_parser_sync(&file_path, self.is_ws_file(&file_path), Some(&params.content_changes.get(0).unwrap().text));
-        match result {
-            Ok(parser) => {
-                self.parsers.insert(file_name.to_string(), parser);
-            },
-            Err(e) => {
-                self.client.log_message(MessageType::ERROR, format!("could not parse file: {:?}", e)).await;
-            }
+        let mut document = match self.documents.remove(file_name) {
+            Some(document) => document,
+            None => TextDocument::new(String::new()),
+        };
+
+        // a running check of the diagnostics of an earlier change releases the index
+        super::count_change(self);
+
+        // the index can only be updated in place if it matches the content before the changes
+        let now = Instant::now();
+        let mut reparse = self.parse_errors.read().unwrap().contains_key(file_name);
+        for change in params.content_changes.iter() {
+            let (start, old_end, new_end) = document.apply_change(change.range, &change.text, self.position_encoding);
+            reparse = reparse || match self.parsers.write().unwrap().get_mut(file_name) {
+                Some(parser) => !parser.update(&document.text, start, old_end, new_end),
+                None => true,
+            };
+        }
+
+        if reparse {
+            let _ = self.create_parser(&file_path, Some(&document.text)).await;
+        } else {
+            eprintln!("updating {} took: {:?}", file_name, now.elapsed());
         }
+        self.documents.insert(file_name.to_string(), document);
+
+        super::publish_diagnostics_debounced(self);
     }
 
     async fn did_save(&mut self, params: DidSaveTextDocumentParams) {
         let file_path = params.text_document.uri.to_file_path().unwrap();
 
+        // an open document which is kept in sync is already up to date
+        if self.documents.contains_key(file_path.to_str().unwrap()) {
+            return;
+        }
+
         let _ = self.create_parser(&file_path, None).await;
+        super::publish_diagnostics(self).await;
     }
 
     async fn did_close(&mut self, params: DidCloseTextDocumentParams) {
@@ -110,11 +186,22 @@ impl LanguageServer for Backend {
         let file_path = params.text_document.uri.to_file_path().unwrap();
         let file_name = file_path.to_str().unwrap();
 
-        if let Some(parser) = self.parsers.get(file_name) {
-            if !parser.is_ws_file {
-                self.parsers.remove(file_name);
+        let synced = self.documents.remove(file_name).is_some();
+        self.synced_documents.write().unwrap().remove(file_name);
+
+        if !self.is_ws_file(&file_path) {
+            self.parse_errors.write().unwrap().remove(file_name);
+            if self.parsers.write().unwrap().remove(file_name).is_some() {
                 self.client.log_message(MessageType::INFO, "removing parser!").await;
             }
+            super::publish_diagnostics(self).await;
+        } else if synced {
+            // the index of the buffer may contain unsaved changes, the file has to be indexed again. If it
+            // doesn't parse, the file is left out of the index like one which failed to parse while indexing.
+            if self.create_parser(&file_path, None).await.is_err() {
+                self.parsers.write().unwrap().remove(file_name);
+            }
+            super::publish_diagnostics(self).await;
         }
     }
 
@@ -137,4 +224,48 @@ impl LanguageServer for Backend {
     async fn goto_definition(&self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>> {
         super::goto_definition(self, params).await
     }
+
+    async fn goto_type_definition(&self, params: GotoTypeDefinitionParams) -> Result<Option<GotoTypeDefinitionResponse>> {
+        super::goto_type_definition(self, params).await
+    }
+
+    async fn prepare_rename(&self, params: TextDocumentPositionParams) -> Result<Option<PrepareRenameResponse>> {
+        super::prepare_rename(self, params).await
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        super::rename(self, params).await
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        super::completion(self, params).await
+    }
+
+    async fn semantic_tokens_full(&self, params: SemanticTokensParams) -> Result<Option<SemanticTokensResult>> {
+        super::semantic_tokens_full(self, params).await
+    }
+
+    async fn semantic_tokens_range(&self, params: SemanticTokensRangeParams) -> Result<Option<SemanticTokensRangeResult>> {
+        super::semantic_tokens_range(self, params).await
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        super::folding_range(self, params).await
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        super::code_lens(self, params).await
+    }
+
+    async fn code_lens_resolve(&self, params: CodeLens) -> Result<CodeLens> {
+        super::code_lens_resolve(self, params).await
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        super::inlay_hint(self, params).await
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        super::code_action(self, params).await
+    }
 }
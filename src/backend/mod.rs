@@ -1,14 +1,14 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, RwLock}};
 
 use anyhow::Error;
-use dashmap::DashMap;
 use regex::RegexSet;
 use tokio::time::Instant;
 use tower_lsp::{lsp_types::*, Client};
-use rayon::prelude::*;
 use glob::glob;
 
-use crate::xml_parser::XmlParser;
+use crate::index_cache::IndexCache;
+use crate::text_document::TextDocument;
+use crate::xml_parser::{IdentNode, PositionEncoding, XmlParser};
 
 mod init;
 use init::init;
@@ -22,8 +22,31 @@ mod references;
 use references::references;
 mod goto_definition;
 use goto_definition::goto_definition;
+mod goto_type_definition;
+use goto_type_definition::goto_type_definition;
+mod rename;
+use rename::{prepare_rename, rename};
+mod completion;
+use completion::completion;
+mod code_action;
+use code_action::code_action;
+mod semantic_tokens;
+use semantic_tokens::{semantic_tokens_full, semantic_tokens_range};
+mod folding_range;
+use folding_range::folding_range;
+mod code_lens;
+use code_lens::{code_lens, code_lens_resolve};
+mod inlay_hint;
+use inlay_hint::inlay_hint;
+mod indexing;
+use indexing::{index_files, reindex_files};
+mod diagnostics;
+use diagnostics::{count_change, parse_error_diagnostic, publish_diagnostics, publish_diagnostics_debounced};
 mod language_server;
 
+/// parsers of the indexed files by file name
+type Parsers = HashMap<String, XmlParser>;
+
 struct ClientConfig {
     ignore_regex_set: RegexSet,
 
@@ -31,26 +54,138 @@ struct ClientConfig {
 
 pub struct Backend {
     client: Client,
-    parsers: HashMap<String, XmlParser>,
+    /// shared with the background indexing and the debounced diagnostics, so it must not be held across awaits
+    parsers: Arc<RwLock<Parsers>>,
     ws_folder: Vec<WorkspaceFolder>,
     config: Option<ClientConfig>,
+    parse_errors: Arc<RwLock<HashMap<String, Diagnostic>>>,
+    published_diagnostics: Arc<tokio::sync::Mutex<HashMap<String, Vec<Diagnostic>>>>,
+    /// counts the changes of documents, a debounced publishing of the diagnostics is dropped if another change followed
+    changes: Arc<AtomicUsize>,
+    text_sync: TextDocumentSyncKind,
+    /// encoding of the characters in the positions exchanged with the client
+    position_encoding: PositionEncoding,
+    /// content of the open documents, only kept if the client sends changes
+    documents: HashMap<String, TextDocument>,
+    /// names of the open documents, shared with the background indexing which must not replace their index
+    synced_documents: Arc<RwLock<HashSet<String>>>,
+    /// the client supports registering file watchers at runtime
+    dynamic_file_watching: bool,
+    /// the client supports progress reporting started by the server
+    work_done_progress: bool,
+    /// the client supports requesting the semantic tokens again, e.g. once references can be resolved
+    semantic_tokens_refresh: bool,
+    /// the client supports requesting the code lenses again, e.g. once the references of other files are indexed
+    code_lens_refresh: bool,
+    /// the client supports requesting the inlay hints again, e.g. once the definitions of the values are indexed
+    inlay_hint_refresh: bool,
+    /// cancellation flag of the running background indexing
+    indexing: Option<Arc<AtomicBool>>,
+    index_cache: Option<Arc<IndexCache>>,
 }
 
 impl Backend {
     pub fn new(client: Client) -> Self {
         Backend {
             client: client,
-            parsers: HashMap::new(),
+            parsers: Arc::new(RwLock::new(HashMap::new())),
             ws_folder: Vec::new(),
             config: None,
+            parse_errors: Arc::new(RwLock::new(HashMap::new())),
+            published_diagnostics: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            changes: Arc::new(AtomicUsize::new(0)),
+            text_sync: TextDocumentSyncKind::NONE,
+            position_encoding: PositionEncoding::default(),
+            documents: HashMap::new(),
+            synced_documents: Arc::new(RwLock::new(HashSet::new())),
+            dynamic_file_watching: false,
+            work_done_progress: false,
+            semantic_tokens_refresh: false,
+            code_lens_refresh: false,
+            inlay_hint_refresh: false,
+            indexing: None,
+            index_cache: None,
+        }
+    }
+
+    /// pulls the configuration from the client, the current configuration is kept if it can't be used
+    async fn update_config(&mut self) {
+        let result = self.client.configuration(vec![ConfigurationItem{scope_uri: None, section: Some("arxmlLanguageServer.ignorePattern".to_string()) }]).await;
+
+        let patterns = result.ok()
+            .and_then(|values| values.into_iter().next())
+            .and_then(|value| value.as_array().map(|patterns| patterns.iter().filter_map(|x| x.as_str().map(str::to_string)).collect::<Vec<String>>()));
+
+        match patterns.map(RegexSet::new) {
+            Some(Ok(regexs)) => {
+                self.config = Some(ClientConfig {
+                    ignore_regex_set: regexs,
+                });
+            },
+            Some(Err(e)) => {
+                self.client.log_message(MessageType::ERROR, format!("invalid ignore pattern: {}", e)).await;
+            },
+            None => {
+                self.client.log_message(MessageType::ERROR, "could not get the ignore pattern").await;
+            },
+        }
 
+        if self.config.is_none() {
+            self.config = Some(ClientConfig {
+                ignore_regex_set: RegexSet::empty(),
+            });
         }
     }
 
-    fn parse_ws(&mut self) -> Vec<String> {
+    /// starts indexing the workspace in the background
+    fn parse_ws(&mut self) {
+        let files = self.ws_files();
+        index_files(self, files);
+    }
+
+    /// stops the running background indexing, parsers it didn't add yet are dropped
+    fn cancel_indexing(&mut self) {
+        if let Some(cancelled) = self.indexing.take() {
+            cancelled.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// brings the parsers in line with the workspace files after the workspace folders or the configuration changed.
+    /// Parsers of files which aren't workspace files anymore are dropped unless the file is open, new files are
+    /// indexed in the background.
+    fn update_ws_files(&mut self) {
+        self.cancel_indexing();
 
-        let errors = DashMap::new();
-        errors.insert("errors", Vec::new());
+        let files = self.ws_files();
+        let file_names: HashSet<&str> = files.iter().map(|file| file.to_str().unwrap()).collect();
+
+        let documents = &self.documents;
+        let mut parsers = self.parsers.write().unwrap();
+        let mut parse_errors = self.parse_errors.write().unwrap();
+        parsers.retain(|file_name, parser| {
+            let was_ws_file = parser.is_ws_file;
+            parser.is_ws_file = file_names.contains(file_name.as_str());
+            parser.is_ws_file || !was_ws_file || documents.contains_key(file_name)
+        });
+        parse_errors.retain(|file_name, _| {
+            file_names.contains(file_name.as_str()) || parsers.contains_key(file_name) || documents.contains_key(file_name)
+        });
+
+        let new_files: Vec<PathBuf> = files.iter()
+            .filter(|file| {
+                let file_name = file.to_str().unwrap();
+                !parsers.contains_key(file_name) && !parse_errors.contains_key(file_name) && !documents.contains_key(file_name)
+            })
+            .cloned()
+            .collect();
+        drop(parsers);
+        drop(parse_errors);
+
+        index_files(self, new_files);
+    }
+
+    /// returns the ARXML files in the workspace folders which aren't ignored
+    fn ws_files(&self) -> Vec<PathBuf> {
 
         let mut files: Vec<Result<PathBuf, glob::GlobError>> = Vec::new();
 
@@ -63,21 +198,11 @@ impl Backend {
         });
 
         // create a vec of type Vec<PathBuf> containing files where the result is Ok and where is_ws_file returns true
-        let files: Vec<PathBuf> = files
+        files
             .into_iter()
             .filter_map(|file| file.ok())
             .filter(|file| self.is_ws_file(file))
-            .collect();
-    
-        self.parsers.par_extend(files.par_iter().filter_map(|file: &PathBuf| {
-            if let Ok(parser) = Backend::create_parser_sync(file, true, None) {
-                Some((file.to_str().unwrap().to_string(), parser))
-            } else {
-                None
-            }
-        }));
-
-        let x = errors.get("errors").unwrap().to_vec(); x
+            .collect()
     }
 
     fn is_ws_file(&self, file: &PathBuf) -> bool {
@@ -90,16 +215,18 @@ impl Backend {
         return false;
     }
 
-    fn create_parser_sync(file: &PathBuf, is_ws_file:bool, content:Option<&str>) -> core::result::Result<XmlParser, Error> {
+    /// parses the content, or the file if there is none. Files are taken from the index cache if it is given.
+    fn create_parser_sync(file: &PathBuf, is_ws_file:bool, content:Option<&str>, index_cache: Option<&IndexCache>) -> core::result::Result<XmlParser, Error> {
         
         let file_name = file.to_str().unwrap();
 
         let mut parser = XmlParser::new(file_name, is_ws_file);
 
         let now = Instant::now();
-        let result = match content {
-            Some(content) => parser.parse(content),
-            None => parser.parse_file()
+        let result = match (content, index_cache) {
+            (Some(content), _) => parser.parse(content),
+            (None, Some(index_cache)) => index_cache.parse_file(file_name, is_ws_file).map(|cached| parser = cached),
+            (None, None) => parser.parse_file()
         };
         
         match result {
@@ -113,19 +240,50 @@ impl Backend {
         }
     }
 
+    /// creates the diagnostic of a file which doesn't parse, a file which wasn't parsed from the given content is read
+    /// again to find the position of the error
+    fn parse_error(file: &Path, content: Option<&str>, error: &Error, encoding: PositionEncoding) -> Diagnostic {
+        match content {
+            Some(content) => parse_error_diagnostic(error, Some(content), encoding),
+            None => {
+                let content = XmlParser::new(file.to_str().unwrap(), false).read_content().ok();
+                parse_error_diagnostic(error, content.as_deref(), encoding)
+            },
+        }
+    }
+
     async fn create_parser(&mut self, file: &PathBuf, content:Option<&str>)  -> core::result::Result<(), Error> {
-        let result = Backend::create_parser_sync(file, self.is_ws_file(file), content);
+        let result = Backend::create_parser_sync(file, self.is_ws_file(file), content, self.index_cache.as_deref());
         let file_name = file.to_str().unwrap();
         match result {
             Ok(parser) => {
-                self.parsers.insert(file_name.to_string(), parser);
+                self.parse_errors.write().unwrap().remove(file_name);
+                self.parsers.write().unwrap().insert(file_name.to_string(), parser);
                 Ok(())
             },
             Err(e) => {
-                self.parsers.remove(file_name);
+                // the last good parser is kept, so navigation keeps working while the file is being edited
                 self.client.log_message(MessageType::ERROR, format!("could not parse file: {:?}", e)).await;
+                let diagnostic = Backend::parse_error(file, content, &e, self.position_encoding);
+                self.parse_errors.write().unwrap().insert(file_name.to_string(), diagnostic);
                 Err(e)
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// returns the nodes of all parsers with the path, references into a refined module are resolved with the vendor mapping
+fn find_ident_nodes<'a>(parsers: &'a Parsers, path: &str) -> Vec<&'a IdentNode> {
+    let mut nodes = Vec::new();
+
+    parsers.iter().for_each(|(_, parser)| {
+        nodes.extend(parser.ident_nodes.get(path));
+        if let Some(vendor_mapping) = &parser.vendor_mapping {
+            if path.starts_with(vendor_mapping.1.as_str()) {
+                nodes.extend(parser.ident_nodes.get(path.replace(vendor_mapping.1.as_str(), vendor_mapping.0.as_str()).as_str()));
+            }
+        }
+    });
+
+    nodes
+}
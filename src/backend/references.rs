@@ -6,9 +6,10 @@ use super::Backend;
 pub async fn references(backend: &Backend, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
     let file_path = params.text_document_position.text_document.uri.to_file_path().unwrap();
     let file_name = file_path.to_str().unwrap();
-    if let Some(parser) = backend.parsers.get(file_name) {
+    let parsers = backend.parsers.read().unwrap();
+    if let Some(parser) = parsers.get(file_name) {
 
-        let node = parser.get_ident_node_at(params.text_document_position.position.line as usize, params.text_document_position.position.character as usize);
+        let node = parser.get_ident_node_at(params.text_document_position.position, backend.position_encoding);
 
         if node.is_some() {
 
@@ -19,13 +20,13 @@ pub async fn references(backend: &Backend, params: ReferenceParams) -> Result<Op
 
             let vendor_mapping = &parser.vendor_mapping;
 
-            for (_, parser) in backend.parsers.iter() {
+            for (_, parser) in parsers.iter() {
                 // eprintln!("parser: {:?}", parser.file);
                 let mut nodes = Vec::new();
                 nodes.push(parser.refs.get(node_path));
                 if let Some(vendor_mapping) = vendor_mapping {
                     if node_path.starts_with(vendor_mapping.0.as_str()) {
-                        nodes.push(parser.refs.get(&node_path.replace(vendor_mapping.0.as_str(), vendor_mapping.1.as_str())));
+                        nodes.push(parser.refs.get(node_path.replace(vendor_mapping.0.as_str(), vendor_mapping.1.as_str()).as_str()));
                     }
                 }
                 for ref_nodes in nodes {
@@ -33,10 +34,7 @@ pub async fn references(backend: &Backend, params: ReferenceParams) -> Result<Op
                         for ref_node in ref_nodes.unwrap() { 
                             let location = Location {
                                 uri: Url::from_file_path(&ref_node.file).unwrap(),
-                                range: Range {
-                                    start: Position::new(ref_node.start.row-1, ref_node.start.col-1),
-                                    end: Position::new(ref_node.end.row-1, ref_node.end.col-1),
-                                },
+                                range: parser.lsp_range(&ref_node.range, backend.position_encoding),
                             };
                             refs.push(location);
                         }
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use tower_lsp::lsp_types::*;
+use tower_lsp::jsonrpc::{Error, Result};
+
+use super::{find_ident_nodes, Backend};
+
+pub async fn prepare_rename(backend: &Backend, params: TextDocumentPositionParams) -> Result<Option<PrepareRenameResponse>> {
+    let file_path = params.text_document.uri.to_file_path().unwrap();
+    let file_name = file_path.to_str().unwrap();
+
+    let parsers = backend.parsers.read().unwrap();
+    if let Some(parser) = parsers.get(file_name) {
+        if let Some(node) = parser.get_short_name_node_at(params.position, backend.position_encoding) {
+            return Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+                range: parser.lsp_range(&node.short_name_range, backend.position_encoding),
+                placeholder: node.short_name.to_string(),
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// renames the SHORT-NAME of every declaration of the path and rewrites all references to it and its descendants
+pub async fn rename(backend: &Backend, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+    let file_path = params.text_document_position.text_document.uri.to_file_path().unwrap();
+    let file_name = file_path.to_str().unwrap();
+    let position = params.text_document_position.position;
+
+    let parsers = backend.parsers.read().unwrap();
+    let node = match parsers.get(file_name).and_then(|parser| parser.get_short_name_node_at(position, backend.position_encoding)) {
+        Some(node) => node,
+        None => return Ok(None),
+    };
+
+    let short_name_regex = Regex::new("^[a-zA-Z][a-zA-Z0-9_]*$").unwrap();
+    if !short_name_regex.is_match(&params.new_name) {
+        return Err(Error::invalid_params(format!("{} is not a valid SHORT-NAME", params.new_name)));
+    }
+
+    let old_path = node.path.as_str();
+    let new_path = format!("{}/{}", old_path.rsplit_once('/').unwrap().0, params.new_name);
+    if !find_ident_nodes(&parsers, &new_path).is_empty() {
+        return Err(Error::invalid_params(format!("{} already exists", new_path)));
+    }
+
+    // references into a refined module may use the standardized path
+    let mut renamed_paths = vec![(old_path.to_string(), new_path.clone())];
+    for parser in parsers.values() {
+        let declared = parser.ident_nodes.contains_key(old_path) || parser.duplicate_nodes.iter().any(|node| node.path == old_path);
+        if let Some(vendor_mapping) = &parser.vendor_mapping {
+            if declared && old_path.starts_with(vendor_mapping.0.as_str()) {
+                renamed_paths.push((
+                    old_path.replacen(vendor_mapping.0.as_str(), vendor_mapping.1.as_str(), 1),
+                    new_path.replacen(vendor_mapping.0.as_str(), vendor_mapping.1.as_str(), 1),
+                ));
+            }
+        }
+    }
+
+    let mut changes: HashMap<String, Vec<TextEdit>> = HashMap::new();
+
+    for (file, parser) in parsers.iter() {
+        let mut edits = Vec::new();
+
+        // an AR-PACKAGE may be declared in several files, or several times in the same file
+        let declarations = parser.ident_nodes.get(old_path).into_iter()
+            .chain(parser.duplicate_nodes.iter().filter(|node| node.path == old_path));
+        for declaration in declarations {
+            edits.push(TextEdit {
+                range: parser.lsp_range(&declaration.short_name_range, backend.position_encoding),
+                new_text: params.new_name.to_string(),
+            });
+        }
+
+        for (text, ref_nodes) in parser.refs.iter() {
+            let new_text = renamed_paths.iter().find_map(|(old_path, new_path)| {
+                let rest = text.strip_prefix(old_path.as_str())?;
+                if rest.is_empty() || rest.starts_with('/') {
+                    Some(format!("{}{}", new_path, rest))
+                } else {
+                    None
+                }
+            });
+
+            if let Some(new_text) = new_text {
+                for ref_node in ref_nodes {
+                    edits.push(TextEdit {
+                        range: parser.lsp_range(&ref_node.text_range, backend.position_encoding),
+                        new_text: new_text.to_string(),
+                    });
+                }
+            }
+        }
+
+        if !edits.is_empty() {
+            // the positions of a file which currently doesn't parse belong to an outdated index
+            if backend.parse_errors.read().unwrap().contains_key(file) {
+                return Err(Error::invalid_params(format!("{} contains errors, fix them before renaming", file)));
+            }
+            changes.insert(file.to_string(), edits);
+        }
+    }
+
+    let changes = changes.into_iter()
+        .filter_map(|(file, edits)| Some((Url::from_file_path(file).ok()?, edits)))
+        .collect();
+
+    Ok(Some(WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }))
+}
@@ -0,0 +1,145 @@
+use tower_lsp::lsp_types::*;
+use tower_lsp::jsonrpc::Result;
+
+use crate::xml_parser::{PositionEncoding, XmlParser};
+
+use super::{find_ident_nodes, Backend, Parsers};
+
+/// references which can't be resolved, clients can style them like the `unresolved-reference` diagnostic
+const UNRESOLVED_REFERENCE: SemanticTokenType = SemanticTokenType::new("unresolvedReference");
+
+/// the token types in the order of the legend, a token refers to its type by the index
+const TOKEN_TYPES: [SemanticTokenType; 10] = [
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::CLASS,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::VARIABLE,
+    UNRESOLVED_REFERENCE,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::STRING,
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+];
+
+const TOKEN_MODIFIERS: [SemanticTokenModifier; 1] = [
+    SemanticTokenModifier::DECLARATION,
+];
+
+/// bit of the declaration modifier in the modifiers of a token
+const DECLARATION: u32 = 1 << 0;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+pub async fn semantic_tokens_full(backend: &Backend, params: SemanticTokensParams) -> Result<Option<SemanticTokensResult>> {
+    let file_path = params.text_document.uri.to_file_path().unwrap();
+    let file_name = file_path.to_str().unwrap();
+
+    let parsers = backend.parsers.read().unwrap();
+    Ok(parsers.get(file_name).map(|parser| {
+        SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: encode(semantic_tokens(&parsers, parser, backend.position_encoding), None),
+        })
+    }))
+}
+
+pub async fn semantic_tokens_range(backend: &Backend, params: SemanticTokensRangeParams) -> Result<Option<SemanticTokensRangeResult>> {
+    let file_path = params.text_document.uri.to_file_path().unwrap();
+    let file_name = file_path.to_str().unwrap();
+
+    let parsers = backend.parsers.read().unwrap();
+    Ok(parsers.get(file_name).map(|parser| {
+        SemanticTokensRangeResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: encode(semantic_tokens(&parsers, parser, backend.position_encoding), Some(params.range)),
+        })
+    }))
+}
+
+/// a token on a single line with the index of its type and the bits of its modifiers
+struct Token {
+    range: Range,
+    token_type: u32,
+    modifiers: u32,
+}
+
+fn token_type(token_type: &SemanticTokenType) -> u32 {
+    TOKEN_TYPES.iter().position(|known| known == token_type).expect("token types are part of the legend") as u32
+}
+
+/// the short names, references and values of the file, in no particular order
+fn semantic_tokens(parsers: &Parsers, parser: &XmlParser, encoding: PositionEncoding) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut push = |range: &std::ops::Range<usize>, typ: &SemanticTokenType, modifiers: u32| {
+        // values may span several lines, most clients only support tokens on a single line
+        for range in parser.lsp_line_ranges(range, encoding) {
+            if range.start != range.end {
+                tokens.push(Token { range, token_type: token_type(typ), modifiers });
+            }
+        }
+    };
+
+    for node in parser.ident_nodes.values().chain(parser.duplicate_nodes.iter()) {
+        let typ = if node.node.tag_name == "AR-PACKAGE" { SemanticTokenType::NAMESPACE } else { SemanticTokenType::CLASS };
+        push(&node.short_name_range, &typ, DECLARATION);
+
+        for value in node.values.iter() {
+            let typ = match value.typ {
+                SymbolKind::ENUM => SemanticTokenType::ENUM_MEMBER,
+                SymbolKind::NUMBER => SemanticTokenType::NUMBER,
+                SymbolKind::BOOLEAN => SemanticTokenType::KEYWORD,
+                SymbolKind::STRING => SemanticTokenType::STRING,
+                SymbolKind::FUNCTION => SemanticTokenType::FUNCTION,
+                // the value of a reference is highlighted as a reference
+                _ => continue,
+            };
+            push(&value.value_range, &typ, 0);
+        }
+    }
+
+    for (path, ref_nodes) in parser.refs.iter() {
+        let resolved = !find_ident_nodes(parsers, path).is_empty();
+        for ref_node in ref_nodes {
+            let typ = if !resolved {
+                UNRESOLVED_REFERENCE
+            } else if ref_node.tag_name == "DEFINITION-REF" {
+                SemanticTokenType::TYPE
+            } else {
+                SemanticTokenType::VARIABLE
+            };
+            push(&ref_node.text_range, &typ, 0);
+        }
+    }
+
+    tokens
+}
+
+/// encodes the tokens relative to each other, only the tokens which overlap the range are kept
+fn encode(mut tokens: Vec<Token>, range: Option<Range>) -> Vec<SemanticToken> {
+    if let Some(range) = range {
+        tokens.retain(|token| token.range.end > range.start && token.range.start < range.end);
+    }
+    tokens.sort_by_key(|token| (token.range.start.line, token.range.start.character));
+
+    let mut previous = Position::new(0, 0);
+    tokens.iter().map(|token| {
+        let start = token.range.start;
+        let delta_line = start.line - previous.line;
+        let delta_start = if delta_line == 0 { start.character - previous.character } else { start.character };
+        previous = start;
+
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length: token.range.end.character - start.character,
+            token_type: token.token_type,
+            token_modifiers_bitset: token.modifiers,
+        }
+    }).collect()
+}
@@ -13,7 +13,8 @@ pub async fn symbol(backend: &Backend, params: WorkspaceSymbolParams) -> Result<
 
         let mut vec_id_nodes: Vec<Vec<SymbolInformation>> = Vec::new();
 
-        vec_id_nodes.par_extend(backend.parsers.par_iter().map(| (_, parser) | {
+        let parsers = backend.parsers.read().unwrap();
+        vec_id_nodes.par_extend(parsers.par_iter().map(| (_, parser) | {
             let mut symbols = Vec::new();
 
             for node in parser.ident_nodes.values() {
@@ -26,12 +27,9 @@ pub async fn symbol(backend: &Backend, params: WorkspaceSymbolParams) -> Result<
                         tags: None,
                         location: Location {
                             uri: Url::from_file_path(&node.node.file).unwrap(),
-                            range: Range {
-                                start: Position::new(node.node.start.row-1, node.node.start.col-1),
-                                end: Position::new(node.node.end.row-1, node.node.end.col-1),
-                            },
+                            range: parser.lsp_range(&node.node.range, backend.position_encoding),
                         },
-                        container_name: Some(node.path.clone()),
+                        container_name: Some(node.path.to_string()),
                         deprecated: None,
                     };
                     symbols.push(symbol);
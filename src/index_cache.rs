@@ -0,0 +1,150 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Error;
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::xml_parser::XmlParser;
+
+/// has to be increased whenever the serialized index changes, entries of other versions are parsed again
+const FORMAT_VERSION: u32 = 10;
+
+/// index of the parsed files stored on disk, so files which didn't change aren't parsed again after a restart
+pub struct IndexCache {
+    dir: PathBuf,
+}
+
+/// describes the file content an entry was created from
+#[derive(Serialize, Deserialize)]
+struct Header {
+    file: String,
+    size: u64,
+    modified: SystemTime,
+    hash: Vec<u8>,
+}
+
+impl IndexCache {
+    pub fn new(dir: PathBuf) -> Self {
+        IndexCache { dir }
+    }
+
+    /// the cache directory of the user, or the temporary directory if there is none
+    pub fn default_dir() -> PathBuf {
+        std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir)
+            .join("arxml_lang_server")
+    }
+
+    /// parses the file, unless it didn't change since its index was stored. Entries which can't be read are
+    /// replaced by parsing the file again.
+    pub fn parse_file(&self, file_name: &str, is_ws_file: bool) -> Result<XmlParser, Error> {
+        let metadata = fs::metadata(file_name)?;
+        let entry = self.entry_path(file_name);
+
+        // filled if the content had to be hashed to compare it
+        let mut hash = None;
+        match self.load(&entry, file_name, &metadata, &mut hash) {
+            Ok(Some(mut parser)) => {
+                parser.is_ws_file = is_ws_file;
+                // a file which was only touched gets a new entry, so the hash isn't computed on every start
+                if let Some(hash) = hash {
+                    self.store_logged(&entry, file_name, &metadata, hash, &parser);
+                }
+                return Ok(parser);
+            },
+            Ok(None) => {},
+            Err(e) => eprintln!("ignoring broken cache entry of {}: {:#}", file_name, e),
+        }
+
+        let hash = match hash {
+            Some(hash) => hash,
+            None => hash_file(file_name)?,
+        };
+        let mut parser = XmlParser::new(file_name, is_ws_file);
+        parser.parse_file()?;
+        self.store_logged(&entry, file_name, &metadata, hash, &parser);
+
+        Ok(parser)
+    }
+
+    fn entry_path(&self, file_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.idx", hex(&Sha256::digest(file_name.as_bytes()))))
+    }
+
+    fn load(&self, entry: &Path, file_name: &str, metadata: &Metadata, hash: &mut Option<Vec<u8>>) -> Result<Option<XmlParser>, Error> {
+        let cache_file = match File::open(entry) {
+            Ok(cache_file) => cache_file,
+            Err(_) => return Ok(None),
+        };
+        // a broken length prefix must not allocate more than the entry contains
+        let options = bincode::DefaultOptions::new().with_limit(cache_file.metadata()?.len());
+        let mut reader = BufReader::new(cache_file);
+
+        let version: u32 = options.deserialize_from(&mut reader)?;
+        if version != FORMAT_VERSION {
+            return Ok(None);
+        }
+        let header: Header = options.deserialize_from(&mut reader)?;
+        if header.file != file_name || header.size != metadata.len() {
+            return Ok(None);
+        }
+        // the modification time also changes if the file was only touched, e.g. by a checkout
+        if metadata.modified().ok() != Some(header.modified) {
+            let unchanged = hash.insert(hash_file(file_name)?) == &header.hash;
+            if !unchanged {
+                return Ok(None);
+            }
+        }
+
+        let mut parser: XmlParser = options.deserialize_from(&mut reader)?;
+        parser.share_strings();
+        Ok(Some(parser))
+    }
+
+    fn store_logged(&self, entry: &Path, file_name: &str, metadata: &Metadata, hash: Vec<u8>, parser: &XmlParser) {
+        if let Err(e) = self.store(entry, file_name, metadata, hash, parser) {
+            eprintln!("could not write cache entry of {}: {:#}", file_name, e);
+        }
+    }
+
+    fn store(&self, entry: &Path, file_name: &str, metadata: &Metadata, hash: Vec<u8>, parser: &XmlParser) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)?;
+
+        let header = Header {
+            file: file_name.to_string(),
+            size: metadata.len(),
+            modified: metadata.modified()?,
+            hash,
+        };
+
+        // written next to the entry and moved, so an interrupted write doesn't leave a partial entry behind
+        let temp_file = entry.with_extension(format!("{}.tmp", std::process::id()));
+        let options = bincode::DefaultOptions::new();
+        let mut writer = BufWriter::new(File::create(&temp_file)?);
+        options.serialize_into(&mut writer, &FORMAT_VERSION)?;
+        options.serialize_into(&mut writer, &header)?;
+        options.serialize_into(&mut writer, parser)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&temp_file, entry)?;
+
+        Ok(())
+    }
+}
+
+/// hashes the content of the file without reading it into memory as a whole
+fn hash_file(file_name: &str) -> Result<Vec<u8>, Error> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(file_name)?, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -3,6 +3,8 @@ use std::env;
 use tokio::net::TcpStream;
 use tower_lsp::{LspService, Server};
 mod xml_parser;
+mod index_cache;
+mod text_document;
 mod backend;
 
 use backend::Backend;
@@ -0,0 +1,69 @@
+use tower_lsp::lsp_types::{Position, Range};
+
+use crate::xml_parser::{LineIndex, PositionEncoding};
+
+/// content of an open document, kept in sync with the changes sent by the client
+pub struct TextDocument {
+    pub text: String,
+    line_index: LineIndex,
+}
+
+impl TextDocument {
+    pub fn new(text: String) -> Self {
+        let line_index = LineIndex::new(&text);
+        TextDocument { text, line_index }
+    }
+
+    /// applies a change of the client, a change without range replaces the whole text.
+    /// Returns the start and old end of the replaced text and the end of the inserted text.
+    pub fn apply_change(&mut self, range: Option<Range>, text: &str, encoding: PositionEncoding) -> (usize, usize, usize) {
+        let (start, old_end) = match range {
+            Some(range) => (self.offset_at(range.start, encoding), self.offset_at(range.end, encoding).max(self.offset_at(range.start, encoding))),
+            None => (0, self.text.len()),
+        };
+
+        self.text.replace_range(start..old_end, text);
+        self.line_index.patch(start, old_end, text);
+
+        (start, old_end, start + text.len())
+    }
+
+    /// converts a position into a byte offset, positions behind a line end are clamped
+    fn offset_at(&self, position: Position, encoding: PositionEncoding) -> usize {
+        match self.line_index.offset(position, encoding) {
+            Some(offset) => offset.min(self.text.len()),
+            None => self.text.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Option<Range> {
+        Some(Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)))
+    }
+
+    #[test]
+    fn changes_behind_a_crlf_line_end_are_inserted_before_it() {
+        let mut document = TextDocument::new("<A>ä</A>\r\n<B/>\r\n".to_string());
+
+        document.apply_change(range((0, 100), (0, 100)), "x", PositionEncoding::Utf16);
+        assert_eq!(document.text, "<A>ä</A>x\r\n<B/>\r\n");
+
+        // the whole line end is replaced
+        document.apply_change(range((0, 100), (1, 0)), "", PositionEncoding::Utf16);
+        assert_eq!(document.text, "<A>ä</A>x<B/>\r\n");
+    }
+
+    #[test]
+    fn changes_count_columns_in_the_encoding() {
+        for (encoding, column) in [(PositionEncoding::Utf8, 7), (PositionEncoding::Utf16, 5), (PositionEncoding::Utf32, 4)] {
+            let mut document = TextDocument::new("\r\n<B>😀x</B>".to_string());
+            let (start, old_end, new_end) = document.apply_change(range((1, column), (1, column + 1)), "ü", encoding);
+            assert_eq!(document.text, "\r\n<B>😀ü</B>");
+            assert_eq!((start, old_end, new_end), (9, 10, 11));
+        }
+    }
+}
@@ -1,76 +1,287 @@
-use std::collections::{BTreeMap, HashMap};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Seek};
+use std::ops::{Bound, Deref};
+use std::sync::Arc;
 
 use anyhow::Error;
-use roxmltree::{Document, Node, TextPos};
-use tower_lsp::lsp_types::SymbolKind;
+use encoding_rs::UTF_8;
+use roxmltree::{Attribute, Document, Node, TextPos};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tower_lsp::lsp_types::{Position, Range, SymbolKind};
 
+mod encoding;
+mod line_index;
+mod stream;
+
+pub use line_index::{LineIndex, PositionEncoding};
+
+/// files above this size are parsed with a pull parser, so the document isn't kept in memory as a whole
+pub const STREAMING_THRESHOLD: usize = 32 * 1024 * 1024;
+
+/// immutable string shared by all nodes with the same text, e.g. the file name, a tag name or the path of the
+/// element containing a reference
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SharedStr(Arc<str>);
+
+impl SharedStr {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for SharedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for SharedStr {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<std::path::Path> for SharedStr {
+    fn as_ref(&self) -> &std::path::Path {
+        std::path::Path::new(&*self.0)
+    }
+}
+
+impl From<&str> for SharedStr {
+    fn from(text: &str) -> Self {
+        SharedStr(Arc::from(text))
+    }
+}
+
+impl From<String> for SharedStr {
+    fn from(text: String) -> Self {
+        SharedStr(Arc::from(text))
+    }
+}
+
+impl PartialEq<str> for SharedStr {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for SharedStr {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl fmt::Display for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Debug for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for SharedStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(SharedStr::from)
+    }
+}
+
+/// serialization of roxmltree's position, which doesn't implement serde itself
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "TextPos")]
+struct TextPosDef {
+    row: u32,
+    col: u32,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct XmlParserNode{
-    pub file: String,
+    pub file: SharedStr,
+    #[serde(with = "TextPosDef")]
     pub start: TextPos,
+    #[serde(with = "TextPosDef")]
     pub end: TextPos,
     pub range: std::ops::Range<usize>,
-    pub tag_name: String,
-    pub def_ref: Option<String>,
+    pub tag_name: SharedStr,
+    pub def_ref: Option<SharedStr>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct IdentNode {
     pub short_name: String,
     pub node: XmlParserNode,
+    #[serde(with = "TextPosDef")]
     pub short_name_start: TextPos,
+    #[serde(with = "TextPosDef")]
     pub short_name_end: TextPos,
     pub short_name_range: std::ops::Range<usize>,
-    pub path: String,
+    /// shared with the key in `ident_nodes` and the nodes inside the element
+    pub path: SharedStr,
     pub values: Vec<ValueNode>,
+    pub multiplicity: Option<Multiplicity>,
+    pub param_def: Option<ParamDef>,
+    /// description of ECUC definition elements
+    pub desc: Option<String>,
+}
+
+/// value constraints of an ECUC parameter definition, bounds are kept as written in the definition
+#[derive(Default, Serialize, Deserialize)]
+pub struct ParamDef {
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub regular_expression: Option<String>,
+    pub default_value: Option<String>,
+    /// the path of the unit of a numerical parameter
+    pub unit: Option<String>,
 }
 
+impl ParamDef {
+    /// takes the text of a constraint element, the first element of each constraint is used
+    fn set(&mut self, tag_name: &str, text: &str) {
+        let text = text.trim();
+        match tag_name {
+            "MIN" => self.min = self.min.take().or(Some(text.to_string())),
+            "MAX" => self.max = self.max.take().or(Some(text.to_string())),
+            "MIN-LENGTH" => self.min_length = self.min_length.or(text.parse::<usize>().ok()),
+            "MAX-LENGTH" => self.max_length = self.max_length.or(text.parse::<usize>().ok()),
+            "REGULAR-EXPRESSION" => self.regular_expression = self.regular_expression.take().or(Some(text.to_string())),
+            "DEFAULT-VALUE" => self.default_value = self.default_value.take().or(Some(text.to_string())),
+            "UNIT-REF" => self.unit = self.unit.take().or(Some(text.to_string())),
+            _ => (),
+        }
+    }
+}
+
+/// multiplicity of an ECUC definition element
+#[derive(Serialize, Deserialize)]
+pub struct Multiplicity {
+    pub lower: u32,
+    /// `None` if the upper multiplicity is infinite
+    pub upper: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct ValueNode {
-    pub short_name: String,
+    /// the path of the value is the path of its container followed by the short name
+    pub short_name: SharedStr,
     pub node: XmlParserNode,
-    pub path: String,
-    pub value: String,
+    pub value: SharedStr,
+    pub value_range: std::ops::Range<usize>,
     pub typ: SymbolKind
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct RefNode {
-    pub file: String,
+    pub file: SharedStr,
+    #[serde(with = "TextPosDef")]
     pub start: TextPos,
+    #[serde(with = "TextPosDef")]
     pub end: TextPos,
     pub range: std::ops::Range<usize>,
-    pub tag_name: String,
+    pub tag_name: SharedStr,
+    #[serde(with = "TextPosDef")]
     pub text_start: TextPos,
+    #[serde(with = "TextPosDef")]
     pub text_end: TextPos,
-    pub text: String,
+    pub text: SharedStr,
     pub text_range: std::ops::Range<usize>,
-    pub path: String,
+    pub path: SharedStr,
+    pub dest: SharedStr,
+    pub dest_range: std::ops::Range<usize>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct XmlParser{
-    pub ident_nodes: BTreeMap<String, IdentNode>,
+    pub ident_nodes: BTreeMap<SharedStr, IdentNode>,
+    /// nodes of this file which were shadowed in `ident_nodes` by a later node with the same path
+    pub duplicate_nodes: Vec<IdentNode>,
     pub value_nodes: Vec<ValueNode>,
-    pub refs: HashMap<String, Vec<RefNode>>,
-    line_offsets: Vec<usize>,
-    pub file: String,
-    last_ident_node: Option<String>,
+    pub refs: HashMap<SharedStr, Vec<RefNode>>,
+    /// ranges of the ADMIN-DATA, DESC and SDGS elements, in no particular order
+    pub blocks: Vec<std::ops::Range<usize>>,
+    line_index: LineIndex,
+    pub file: SharedStr,
+    #[serde(skip)]
+    last_ident_node: Option<SharedStr>,
+    /// texts which occur many times, like tag names or definition references, are shared by the nodes
+    #[serde(skip)]
+    strings: HashSet<SharedStr>,
+    /// offset of the traversed document in the content, an element which is traversed again by `update` is parsed
+    /// on its own
+    #[serde(skip)]
+    document_offset: usize,
     pub is_ws_file: bool,
     pub vendor_mapping: Option<(String, String)>,
 }
 
 impl XmlParser {
     pub fn new(file_name: &str, is_ws_file: bool) -> Self {
-        let xml_parser = XmlParser {ident_nodes: BTreeMap::new(), refs: HashMap::new(), line_offsets: Vec::new(), file: file_name.to_string(), value_nodes: Vec::new(), last_ident_node: None, is_ws_file: is_ws_file, vendor_mapping: None};
+        let xml_parser = XmlParser {ident_nodes: BTreeMap::new(), duplicate_nodes: Vec::new(), refs: HashMap::new(), blocks: Vec::new(), line_index: LineIndex::default(), file: SharedStr::from(file_name), value_nodes: Vec::new(), last_ident_node: None, strings: HashSet::new(), document_offset: 0, is_ws_file: is_ws_file, vendor_mapping: None};
         xml_parser
     }
 
     pub fn parse_file(&mut self) -> Result<(), Error> {
-        let content = std::fs::read_to_string(self.file.to_string())?;
-        self.parse(&content)
+        let mut file = std::fs::File::open(self.file.as_str())?;
+        let mut start = Vec::new();
+        (&mut file).take(encoding::DECLARATION_LENGTH as u64).read_to_end(&mut start)?;
+        let encoding = encoding::detect(&start);
+
+        // the content of other encodings is converted as a whole, large documents are streamed from the converted text
+        if encoding != UTF_8 {
+            let content = encoding::decode(&std::fs::read(self.file.as_str())?, encoding)?;
+            return self.parse(&content);
+        }
+
+        if file.metadata()?.len() > STREAMING_THRESHOLD as u64 {
+            file.rewind()?;
+            if self.parse_stream(std::io::BufReader::new(file)) {
+                return Ok(());
+            }
+        }
+
+        let content = std::fs::read_to_string(self.file.as_str())?;
+        self.parse_document(&content)
+    }
+
+    /// reads the content of the file as it is parsed, content in another encoding than UTF-8 is converted
+    pub fn read_content(&self) -> Result<String, Error> {
+        let content = std::fs::read(self.file.as_str())?;
+        let encoding = encoding::detect(&content);
+        if encoding != UTF_8 {
+            return encoding::decode(&content, encoding);
+        }
+        Ok(String::from_utf8(content)?)
     }
 
     pub fn parse(&mut self, content: &str) -> Result<(), Error> {
+        if content.len() > STREAMING_THRESHOLD && self.parse_stream(content.as_bytes()) {
+            return Ok(());
+        }
+
+        self.parse_document(content)
+    }
+
+    /// parses the content as a whole document, which is traversed afterwards
+    pub fn parse_document(&mut self, content: &str) -> Result<(), Error> {
         // eprintln!("reading file: {}", self.file);
         // let now = Instant::now();
         // let content = std::fs::read_to_string(self.file.to_string())?;
-        self.line_offsets = get_line_offsets(&content);
+        self.line_index = LineIndex::new(content);
         // let elapsed = now.elapsed();
 
         // let now = Instant::now();
@@ -80,7 +291,8 @@ impl XmlParser {
 
         // let now = Instant::now();
         // eprintln!("traversing file");
-        self.traverse_xml("".to_string(), None, &doc.unwrap());
+        self.traverse_xml(SharedStr::from(""), None, &doc.unwrap());
+        self.strings = HashSet::new();
         // let elapsed3 = now.elapsed();
 
         // eprintln!("Read file: {:?}, parse: {:?}, traverse: {:?}", elapsed, elapsed2, elapsed3);
@@ -89,29 +301,33 @@ impl XmlParser {
         Ok(())
     }
 
-    fn traverse_xml<'a>(&mut self, path: String, doc: Option<&Node<'a, 'a>>, doc2: &Document<'a>) {
+    fn traverse_xml<'a>(&mut self, path: SharedStr, doc: Option<&Node<'a, 'a>>, doc2: &Document<'a>) {
         let binding = doc2.root();
         let doc = match doc {
             Some(doc) => doc,
             None => &binding,
         };
         
+        // the ranges of the nodes are relative to the traversed document
+        let offset = self.document_offset;
+        let shift = |range: std::ops::Range<usize>| range.start + offset..range.end + offset;
+
         for child in doc.children() {
             let mut new_path = path.clone();
             let tag_name = child.tag_name().name();
 
             if let Some(short_name) = get_short_name_node(child) {
                 if let Some(short_name_text) = short_name.text() {
-                    new_path.push_str(&format!("/{}", short_name_text));
-                    let (start, end) = self.get_text_pos(child.range());
-                    let(short_name_start, short_name_end) = self.get_text_pos(short_name.first_child().unwrap().range());
+                    new_path = SharedStr::from(format!("{}/{}", path, short_name_text));
+                    let (start, end) = self.get_text_pos(shift(child.range()));
+                    let(short_name_start, short_name_end) = self.get_text_pos(shift(short_name.first_child().unwrap().range()));
 
-                    let def_ref = if tag_name == "ECUC-CONTAINER-VALUE" {
+                    let def_ref = if tag_name == "ECUC-CONTAINER-VALUE" || tag_name == "ECUC-MODULE-CONFIGURATION-VALUES" {
                         if let Some(def_ref) = child.children().find(|child1| child1.tag_name().name() == "DEFINITION-REF") {
                             if let Some(def_ref_text) = def_ref.text() {
-                                Some(def_ref_text.to_string())
+                                Some(self.intern(def_ref_text))
                             } else {
-                                let start_pos = self.get_text_pos(def_ref.range()).0;
+                                let start_pos = self.get_text_pos(shift(def_ref.range())).0;
                                 eprint!("ERROR: No text found for node: {}:{}:{}\n", self.file.to_string(), start_pos.row, start_pos.col);
                                 None
                             }
@@ -126,60 +342,74 @@ impl XmlParser {
                         short_name: short_name_text.to_string(),
                         node: XmlParserNode {
                             // doc: self.doc,
-                            file: self.file.to_string(),
+                            file: self.file.clone(),
                             start: start,
                             end: end,
-                            range: child.range(),
-                            tag_name: tag_name.to_string(),
+                            range: shift(child.range()),
+                            tag_name: self.intern(tag_name),
                             def_ref: def_ref,
 
                         },
                         short_name_start: short_name_start,
                         short_name_end: short_name_end,
-                        short_name_range: short_name.first_child().unwrap().range(),
+                        short_name_range: shift(short_name.first_child().unwrap().range()),
                         path: new_path.clone(),
                         values: Vec::new(),
+                        multiplicity: get_multiplicity(child),
+                        param_def: if is_param_def(tag_name) { Some(get_param_def(child)) } else { None },
+                        desc: if is_ecuc_def(tag_name) { get_desc(child) } else { None },
                     };
-                    self.ident_nodes.insert(new_path.to_owned(), node);
+                    if let Some(duplicate) = self.ident_nodes.insert(new_path.clone(), node) {
+                        self.duplicate_nodes.push(duplicate);
+                    }
                     self.last_ident_node = Some(new_path.clone());
                 } else {
-                    let start_pos = self.get_text_pos(short_name.range()).0;
+                    let start_pos = self.get_text_pos(shift(short_name.range())).0;
                     eprint!("ERROR: No text found for node: {}:{}:{}\n", self.file.to_string(), start_pos.row, start_pos.col);
                 }
                 
             } else if child.has_attribute("DEST") {
-                if let Some(ref_text) = child.text() {
-                    let (start, end) = self.get_text_pos(child.range());
-                    let text_node = child.first_child().unwrap();
-                    let (text_start, text_end) = self.get_text_pos(text_node.range());
+                // an empty reference is kept as well, so it can be completed
+                let text_range = match (child.text(), child.first_child()) {
+                    (Some(_), Some(text_node)) => Some(shift(text_node.range())),
+                    _ => get_empty_content_range(child, doc2.input_text()).map(shift),
+                };
+                if let Some(text_range) = text_range {
+                    let ref_text = child.text().unwrap_or("");
+                    let (start, end) = self.get_text_pos(shift(child.range()));
+                    let (text_start, text_end) = self.get_text_pos(text_range.clone());
+                    let dest = child.attribute_node("DEST").unwrap();
+                    let dest_range = shift(get_attribute_value_range(&dest, doc2.input_text()));
                     let node = RefNode {
                         // doc: self.doc,
-                        file: self.file.to_string(),
+                        file: self.file.clone(),
                         start: start,
                         end: end,
-                        range: child.range(),
-                        tag_name: tag_name.to_string(),
-                        text: ref_text.to_string(),
+                        range: shift(child.range()),
+                        tag_name: self.intern(tag_name),
+                        text: self.intern(ref_text),
                         text_start: text_start,
                         text_end: text_end,
-                        text_range: text_node.range(),
+                        text_range,
                         path: new_path.clone(),
+                        dest: self.intern(dest.value()),
+                        dest_range,
                     };
                     if let Some(ref_vec) = self.refs.get_mut(ref_text) {
                         ref_vec.push(node);
                     } else {
-                        self.refs.insert(ref_text.to_string(), vec![node]);
+                        self.refs.insert(node.text.clone(), vec![node]);
                     }
-                    if tag_name == "REFINED-MODULE-DEF-REF" {
+                    if tag_name == "REFINED-MODULE-DEF-REF" && !ref_text.is_empty() {
                         let module = ref_text;
-                        self.vendor_mapping = Some((new_path.to_owned(), module.to_string()));
+                        self.vendor_mapping = Some((new_path.to_string(), module.to_string()));
                         eprintln!("Vendor Mapping: {} -> {}", new_path, module);
                     }
                 } else {
-                    let start_pos = self.get_text_pos(child.range()).0;
+                    let start_pos = self.get_text_pos(shift(child.range())).0;
                     eprint!("ERROR: No text found for ref node: {}:{}:{}\n", self.file.to_string(), start_pos.row, start_pos.col);
                 }
-            } else if tag_name == "ECUC-CONTAINER-VALUE" || tag_name == "ECUC-REFERENCE-VALUE" || tag_name == "ECUC-NUMERICAL-PARAM-VALUE" || tag_name == "ECUC-TEXTUAL-PARAM-VALUE" {
+            } else if is_value(tag_name) {
                 let def_ref_node = child.children().find(|child| child.tag_name().name() == "DEFINITION-REF");
 
                 if def_ref_node.is_some() {
@@ -187,32 +417,22 @@ impl XmlParser {
                     if let Some(def_ref_text) = def_ref_node.text() {
                         let def_ref = def_ref_text;
                         let name = def_ref.split('/').last().unwrap();
-                        let (start, end) = self.get_text_pos(child.range());
-                        let path = format!("{}/{}", new_path, name);
-                        let dest_type = def_ref_node.attribute("DEST");
-
-                        let typ = match tag_name {
-                            "ECUC-TEXTUAL-PARAM-VALUE" => {
-                                match dest_type {
-                                    Some("ECUC-ENUMERATION-PARAM-DEF") => SymbolKind::ENUM,
-                                    Some("ECUC-FUNCTION-NAME-DEF") => SymbolKind::FUNCTION,
-                                    _ => SymbolKind::STRING,
-                                }
-                            },
-                            "ECUC-NUMERICAL-PARAM-VALUE" => {
-                                match dest_type {
-                                    Some("ECUC-BOOLEAN-PARAM-DEF") => SymbolKind::BOOLEAN,
-                                    _ => SymbolKind::NUMBER,
-                                }
-                            },
-                            _ => SymbolKind::VARIABLE,
-                        };
+                        let (start, end) = self.get_text_pos(shift(child.range()));
+                        let typ = get_value_kind(tag_name, def_ref_node.attribute("DEST"));
 
                         let value = if tag_name == "ECUC-REFERENCE-VALUE" {
                             child.children().find(|child| child.tag_name().name() == "VALUE-REF")
                         } else {
                             child.children().find(|child| child.tag_name().name() == "VALUE")
                         };
+                        // the range of a value without content is the value element itself
+                        let value_range = match value {
+                            Some(value) => match value.first_child() {
+                                Some(text_node) => shift(text_node.range()),
+                                None => get_empty_content_range(value, doc2.input_text()).map(shift).unwrap_or(shift(value.range())),
+                            },
+                            None => shift(child.range()),
+                        };
                         let value = match value {
                             Some(value) => {
                                 match value.text() {
@@ -224,46 +444,197 @@ impl XmlParser {
                         };
 
                         let value = ValueNode {
-                            short_name: name.to_string(),
+                            short_name: self.intern(name),
                             node: XmlParserNode {
                                 // doc: self.doc,
-                                file: self.file.to_string(),
+                                file: self.file.clone(),
                                 start: start,
                                 end: end,
-                                range: child.range(),
-                                tag_name: tag_name.to_string(),
-                                def_ref: Some(def_ref.to_string()),
+                                range: shift(child.range()),
+                                tag_name: self.intern(tag_name),
+                                def_ref: Some(self.intern(def_ref)),
                             },
-                            path: path.clone(),
-                            value: value.to_string(),
+                            value: self.intern(value),
+                            value_range,
                             typ: typ
                         };
                         self.ident_nodes.get_mut(self.last_ident_node.as_ref().unwrap()).unwrap().values.push(value);
                     } else {
-                        let start_pos = self.get_text_pos(def_ref_node.range()).0;
+                        let start_pos = self.get_text_pos(shift(def_ref_node.range())).0;
                         eprint!("ERROR: No text found for node: {}:{}:{}\n", self.file.to_string(), start_pos.row, start_pos.col);
                     }
                 }
             }
+            if is_block(tag_name) {
+                self.blocks.push(shift(child.range()));
+            }
             self.traverse_xml(new_path, Some(&child), doc2);
         }
     }
 
 
+    /// applies an edit of the content by traversing only the innermost identifiable element around it again,
+    /// everything behind the edit is shifted. Returns false if the edit can't be applied this way and the
+    /// whole content has to be parsed again.
+    pub fn update(&mut self, content: &str, start: usize, old_end: usize, new_end: usize) -> bool {
+        let delta = new_end as isize - old_end as isize;
+        let shift = |offset: usize| if offset >= old_end { (offset as isize + delta) as usize } else { offset };
+
+        let enclosing = self.ident_nodes.values().chain(self.duplicate_nodes.iter())
+            .filter(|node| node.node.range.start < start && old_end < node.node.range.end)
+            .min_by_key(|node| node.node.range.len());
+        let (old_range, tag_name, path) = match enclosing {
+            Some(node) => (node.node.range.clone(), node.node.tag_name.clone(), node.path.clone()),
+            None => return false,
+        };
+        let (element, inserted) = match (content.get(old_range.start..shift(old_range.end)), content.get(start..new_end)) {
+            (Some(element), Some(inserted)) => (element, inserted),
+            _ => return false,
+        };
+
+        // only the element is parsed, its ranges are moved to its offset in the content while it is traversed
+        let doc = match Document::parse(element) {
+            Ok(doc) => doc,
+            Err(_) => return false,
+        };
+        let root = doc.root_element();
+        if tag_name != root.tag_name().name() || get_short_name_node(root).and_then(|node| node.text()).is_none() {
+            return false;
+        }
+
+        // drop everything which was found in the old element
+        let contained = |range: &std::ops::Range<usize>| old_range.start <= range.start && range.end <= old_range.end;
+        self.ident_nodes.retain(|_, node| !contained(&node.node.range));
+        self.duplicate_nodes.retain(|node| !contained(&node.node.range));
+        self.refs.retain(|_, ref_nodes| {
+            ref_nodes.retain(|ref_node| !contained(&ref_node.range));
+            !ref_nodes.is_empty()
+        });
+        self.blocks.retain(|block| !contained(block));
+        if let Some((module, _)) = &self.vendor_mapping {
+            if module == path.as_str() || module.starts_with(&format!("{}/", path)) {
+                self.vendor_mapping = None;
+            }
+        }
+
+        // move everything behind the edit
+        self.line_index.patch(start, old_end, inserted);
+        let line_index = &self.line_index;
+        let shift_range = |range: &mut std::ops::Range<usize>| {
+            if range.end >= start {
+                *range = shift(range.start)..shift(range.end);
+            }
+        };
+        let update_range = |range: &mut std::ops::Range<usize>, range_start: &mut TextPos, range_end: &mut TextPos| {
+            if range.end >= start {
+                shift_range(range);
+                (*range_start, *range_end) = line_index.text_pos(range.clone());
+            }
+        };
+        for node in self.ident_nodes.values_mut().chain(self.duplicate_nodes.iter_mut()) {
+            update_range(&mut node.node.range, &mut node.node.start, &mut node.node.end);
+            update_range(&mut node.short_name_range, &mut node.short_name_start, &mut node.short_name_end);
+            for value in node.values.iter_mut() {
+                update_range(&mut value.node.range, &mut value.node.start, &mut value.node.end);
+                shift_range(&mut value.value_range);
+            }
+        }
+        for block in self.blocks.iter_mut() {
+            *block = shift(block.start)..shift(block.end);
+        }
+        for ref_node in self.refs.values_mut().flatten() {
+            update_range(&mut ref_node.range, &mut ref_node.start, &mut ref_node.end);
+            update_range(&mut ref_node.text_range, &mut ref_node.text_start, &mut ref_node.text_end);
+            shift_range(&mut ref_node.dest_range);
+        }
+
+        let parent_path = path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default();
+        self.last_ident_node = None;
+        self.document_offset = old_range.start;
+        self.traverse_xml(SharedStr::from(parent_path), None, &doc);
+        self.document_offset = 0;
+        self.strings = HashSet::new();
+
+        // a node which was shadowed by a node of the old element takes its place again
+        let (restored, duplicates): (Vec<IdentNode>, Vec<IdentNode>) = self.duplicate_nodes.drain(..)
+            .partition(|node| !self.ident_nodes.contains_key(&node.path));
+        self.duplicate_nodes = duplicates;
+        for node in restored {
+            if let Some(duplicate) = self.ident_nodes.insert(node.path.clone(), node) {
+                self.duplicate_nodes.push(duplicate);
+            }
+        }
+
+        true
+    }
+
+    fn intern(&mut self, text: &str) -> SharedStr {
+        match self.strings.get(text) {
+            Some(shared) => shared.clone(),
+            None => {
+                let shared = SharedStr::from(text);
+                self.strings.insert(shared.clone());
+                shared
+            },
+        }
+    }
+
+    /// shares the texts of nodes which were created separately, e.g. by deserialization
+    pub fn share_strings(&mut self) {
+        let file = self.file.clone();
+        let mut strings = std::mem::take(&mut self.strings);
+        let mut intern = |text: &mut SharedStr| {
+            match strings.get(text.as_str()) {
+                Some(shared) => *text = shared.clone(),
+                None => { strings.insert(text.clone()); },
+            }
+        };
+
+        let ident_nodes = std::mem::take(&mut self.ident_nodes);
+        for (_, mut node) in ident_nodes {
+            node.node.file = file.clone();
+            intern(&mut node.node.tag_name);
+            if let Some(def_ref) = node.node.def_ref.as_mut() {
+                intern(def_ref);
+            }
+            for value in node.values.iter_mut() {
+                value.node.file = file.clone();
+                intern(&mut value.short_name);
+                intern(&mut value.node.tag_name);
+                if let Some(def_ref) = value.node.def_ref.as_mut() {
+                    intern(def_ref);
+                }
+                intern(&mut value.value);
+            }
+            // the path is shared with the key and the references inside the element
+            intern(&mut node.path);
+            self.ident_nodes.insert(node.path.clone(), node);
+        }
+
+        let refs = std::mem::take(&mut self.refs);
+        for (_, mut ref_nodes) in refs {
+            for ref_node in ref_nodes.iter_mut() {
+                ref_node.file = file.clone();
+                intern(&mut ref_node.tag_name);
+                intern(&mut ref_node.text);
+                intern(&mut ref_node.path);
+                intern(&mut ref_node.dest);
+            }
+            self.refs.insert(ref_nodes[0].text.clone(), ref_nodes);
+        }
+    }
+
     fn get_text_pos(&mut self, range: std::ops::Range<usize>) -> (TextPos, TextPos) {
-        let start = range.start;
-        let end = range.end;
-        
-        let start_line = self.line_offsets.partition_point(|&x| x <= start);
-        let end_line = self.line_offsets.partition_point(|&x| x <= end);
-        let start_row = start - self.line_offsets[start_line-1]+1;
-        let end_row = end - self.line_offsets[end_line-1]+1;
+        self.line_index.text_pos(range)
+    }
 
-        (TextPos::new(start_line as u32, start_row as u32), TextPos::new(end_line as u32, end_row as u32))
+    /// converts the position into a byte offset into the content
+    pub fn offset(&self, position: Position, encoding: PositionEncoding) -> Option<usize> {
+        self.line_index.offset(position, encoding)
     }
 
-    pub fn get_ident_node_at(&self, line: usize, position: usize) -> Option<&IdentNode> {
-        let offset = self.line_offsets.get(line)? + position;
+    pub fn get_ident_node_at(&self, position: Position, encoding: PositionEncoding) -> Option<&IdentNode> {
+        let offset = self.line_index.offset(position, encoding)?;
 
         for node in self.ident_nodes.values().rev() {
             let start = node.node.range.start;
@@ -275,44 +646,225 @@ impl XmlParser {
         None
     }
 
-    pub fn get_ref_text_at(&self, line: usize, position: usize) -> Option<(String, usize)> {
-        let offset = self.line_offsets.get(line)? + position;
+    /// returns all nodes whose path starts with the prefix, ordered by path
+    pub fn get_nodes_with_prefix<'a, 'p>(&'a self, prefix: &'p str) -> impl Iterator<Item = &'a IdentNode> + 'p where 'a: 'p {
+        self.ident_nodes.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(path, _)| path.starts_with(prefix))
+            .map(|(_, node)| node)
+    }
+
+    /// returns the node whose SHORT-NAME is at the position
+    pub fn get_short_name_node_at(&self, position: Position, encoding: PositionEncoding) -> Option<&IdentNode> {
+        let offset = self.line_index.offset(position, encoding)?;
+
+        self.ident_nodes.values().chain(self.duplicate_nodes.iter())
+            .find(|node| node.short_name_range.start <= offset && offset <= node.short_name_range.end)
+    }
+
+    /// returns the value at the position and the byte offset of the position into the value text
+    pub fn get_value_node_at(&self, position: Position, encoding: PositionEncoding) -> Option<(&ValueNode, usize)> {
+        let offset = self.line_index.offset(position, encoding)?;
+
+        for node in self.ident_nodes.values().chain(self.duplicate_nodes.iter()).flat_map(|node| node.values.iter()) {
+            let start = node.value_range.start;
+            let end = node.value_range.end;
+            if start <= offset && offset <= end {
+                return Some((node, offset - start));
+            }
+        }
+        None
+    }
+
+    /// returns the value whose element contains the position
+    pub fn get_value_element_at(&self, position: Position, encoding: PositionEncoding) -> Option<&ValueNode> {
+        let offset = self.line_index.offset(position, encoding)?;
+
+        self.ident_nodes.values().chain(self.duplicate_nodes.iter()).flat_map(|node| node.values.iter())
+            .find(|node| node.node.range.start <= offset && offset <= node.node.range.end)
+    }
+
+    /// returns the reference at the position and the byte offset of the position into the reference text
+    pub fn get_ref_node_at(&self, position: Position, encoding: PositionEncoding) -> Option<(&RefNode, usize)> {
+        let offset = self.line_index.offset(position, encoding)?;
 
         for node in self.refs.values().flatten() {
             let start = node.text_range.start;
             let end = node.text_range.end;
             if start <= offset && offset <= end {
-                let curser_pos = offset - start;
-                let text = node.text.as_str();
-                let mut pos: usize = 0;
-                let mut text_part = "".to_owned();
-
-                text.split('/').for_each(|s| {
-                    if s.len() != 0 {
-                        if pos <= curser_pos {
-                            text_part.push_str("/");
-                            text_part.push_str(s);
-                        }
-                        pos = pos + s.len() + 1;
-                    }
-                });
-                return Some((text_part, curser_pos));
+                return Some((node, offset - start));
             }
         }
         None
     }
+
+    /// returns the reference path up to the segment at the position and the range of the path in the file
+    pub fn get_ref_text_at(&self, position: Position, encoding: PositionEncoding) -> Option<(String, std::ops::Range<usize>)> {
+        let (node, curser_pos) = self.get_ref_node_at(position, encoding)?;
+        let text = node.text.as_str();
+        let mut pos: usize = 0;
+        let mut text_part = "".to_owned();
+
+        text.split('/').for_each(|s| {
+            if !s.is_empty() {
+                if pos <= curser_pos {
+                    text_part.push('/');
+                    text_part.push_str(s);
+                }
+                pos = pos + s.len() + 1;
+            }
+        });
+        let start = node.text_range.start;
+        let end = (start + text_part.len()).min(node.text_range.end);
+        Some((text_part, start..end))
+    }
+
+    /// converts the byte range of a node of this file into an LSP range
+    pub fn lsp_range(&self, range: &std::ops::Range<usize>, encoding: PositionEncoding) -> Range {
+        self.line_index.range(range, encoding)
+    }
+
+    /// converts the byte range of a node of this file into one LSP range per line
+    pub fn lsp_line_ranges(&self, range: &std::ops::Range<usize>, encoding: PositionEncoding) -> Vec<Range> {
+        self.line_index.line_ranges(range, encoding)
+    }
+}
+
+/// returns the empty range between the start and end tag of an element without content
+fn get_empty_content_range(node: Node, text: &str) -> Option<std::ops::Range<usize>> {
+    let range = node.range();
+    let element = &text[range.clone()];
+    if element.ends_with("/>") {
+        return None;
+    }
+    let content_start = range.start + element.find('>')? + 1;
+    Some(content_start..content_start)
+}
+
+fn get_attribute_value_range(attribute: &Attribute, text: &str) -> std::ops::Range<usize> {
+    // the position of an attribute points to its name, the value is between the quotes. It is taken from the text, the
+    // value of the attribute has its entities already replaced.
+    let position = attribute.position();
+    let (start, quote) = match text[position..].find(['"', '\'']) {
+        Some(index) => (position + index + 1, &text[position + index..position + index + 1]),
+        None => return position..position,
+    };
+    match text[start..].find(quote) {
+        Some(length) => start..start + length,
+        None => start..text.len(),
+    }
 }
 
-fn get_line_offsets(text: &str) -> Vec<usize> {
-    let mut line_offsets = vec![0];
-    let mut offset = 0;
-    for c in text.chars() {
-        offset += c.len_utf8();
-        if c == '\n' {
-            line_offsets.push(offset);
+fn is_value(tag_name: &str) -> bool {
+    tag_name == "ECUC-CONTAINER-VALUE" || tag_name == "ECUC-REFERENCE-VALUE" || tag_name == "ECUC-NUMERICAL-PARAM-VALUE" || tag_name == "ECUC-TEXTUAL-PARAM-VALUE" || tag_name == "ECUC-INSTANCE-REFERENCE-VALUE"
+}
+
+fn get_value_kind(tag_name: &str, dest_type: Option<&str>) -> SymbolKind {
+    match tag_name {
+        "ECUC-TEXTUAL-PARAM-VALUE" => {
+            match dest_type {
+                Some("ECUC-ENUMERATION-PARAM-DEF") => SymbolKind::ENUM,
+                Some("ECUC-FUNCTION-NAME-DEF") => SymbolKind::FUNCTION,
+                _ => SymbolKind::STRING,
+            }
+        },
+        "ECUC-NUMERICAL-PARAM-VALUE" => {
+            match dest_type {
+                Some("ECUC-BOOLEAN-PARAM-DEF") => SymbolKind::BOOLEAN,
+                _ => SymbolKind::NUMBER,
+            }
+        },
+        _ => SymbolKind::VARIABLE,
+    }
+}
+
+/// bounds of a multiplicity as they are collected from the children of a definition
+#[derive(Default)]
+struct MultiplicityBounds {
+    lower: Option<u32>,
+    upper: Option<u32>,
+    upper_infinite: bool,
+}
+
+impl MultiplicityBounds {
+    fn set(&mut self, tag_name: &str, text: Option<&str>) {
+        match tag_name {
+            "LOWER-MULTIPLICITY" => self.lower = text.and_then(|text| text.trim().parse::<u32>().ok()),
+            "UPPER-MULTIPLICITY" => self.upper = text.and_then(|text| text.trim().parse::<u32>().ok()),
+            "UPPER-MULTIPLICITY-INFINITE" => self.upper_infinite = text.map(|text| text.trim() == "true").unwrap_or(false),
+            _ => (),
+        }
+    }
+
+    fn multiplicity(&self) -> Option<Multiplicity> {
+        if self.lower.is_none() && self.upper.is_none() && !self.upper_infinite {
+            return None;
         }
+
+        // a missing bound defaults to 1
+        Some(Multiplicity {
+            lower: self.lower.unwrap_or(1),
+            upper: if self.upper_infinite { None } else { Some(self.upper.unwrap_or(1)) },
+        })
+    }
+}
+
+fn get_multiplicity(node: Node) -> Option<Multiplicity> {
+    let mut bounds = MultiplicityBounds::default();
+    for child in node.children() {
+        bounds.set(child.tag_name().name(), child.text());
+    }
+    bounds.multiplicity()
+}
+
+/// elements of a block of additional information, which can be folded away
+fn is_block(tag_name: &str) -> bool {
+    tag_name == "ADMIN-DATA" || tag_name == "DESC" || tag_name == "SDGS"
+}
+
+pub fn is_ecuc_def(tag_name: &str) -> bool {
+    tag_name.starts_with("ECUC-") && tag_name.ends_with("-DEF")
+}
+
+fn is_param_def(tag_name: &str) -> bool {
+    tag_name.starts_with("ECUC-") && (tag_name.ends_with("-PARAM-DEF") || tag_name == "ECUC-FUNCTION-NAME-DEF" || tag_name == "ECUC-LINKER-SYMBOL-DEF")
+}
+
+/// elements which hold the constraints of a definition in some AUTOSAR versions
+fn is_variant(tag_name: &str) -> bool {
+    tag_name.ends_with("-VARIANTS") || tag_name.ends_with("-CONDITIONAL")
+}
+
+fn get_param_def(node: Node) -> ParamDef {
+    let mut param_def = ParamDef::default();
+    add_constraints(&mut param_def, node);
+    param_def
+}
+
+/// depending on the AUTOSAR version the constraints are nested in a *-VARIANTS/*-CONDITIONAL element. Other elements
+/// like ADMIN-DATA, SDGS or the literals are skipped, they may contain elements with the names of the constraints.
+fn add_constraints(param_def: &mut ParamDef, node: Node) {
+    for child in node.children() {
+        let tag_name = child.tag_name().name();
+        if is_variant(tag_name) {
+            add_constraints(param_def, child);
+        } else if let Some(text) = child.text() {
+            param_def.set(tag_name, text);
+        }
+    }
+}
+
+fn get_desc(node: Node) -> Option<String> {
+    let desc = node.children().find(|child| child.tag_name().name() == "DESC")?;
+    let text = desc.descendants()
+        .filter(|child| child.is_text())
+        .flat_map(|child| child.text().unwrap_or("").split_whitespace())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
     }
-    line_offsets
 }
 
 fn get_short_name_node<'a>(node: Node<'a, 'a>) -> Option<Node<'a, 'a>> {
@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Error};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// number of bytes at the start of a document which are searched for the XML declaration
+pub const DECLARATION_LENGTH: usize = 1024;
+
+/// detects the encoding of a document from the byte order mark or the encoding declared in the XML declaration.
+/// Documents without either are UTF-8.
+pub fn detect(start: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(start) {
+        return encoding;
+    }
+
+    // UTF-16 without byte order mark, the document starts with '<'
+    if start.starts_with(b"<\0") {
+        return UTF_16LE;
+    }
+    if start.starts_with(b"\0<") {
+        return UTF_16BE;
+    }
+
+    match declared_encoding(start).and_then(Encoding::for_label) {
+        // the declaration could be read, so the document isn't UTF-16 whatever it declares
+        Some(encoding) if encoding == UTF_16LE || encoding == UTF_16BE => UTF_8,
+        Some(encoding) => encoding,
+        None => UTF_8,
+    }
+}
+
+/// returns the value of the encoding declaration, e.g. `ISO-8859-1` for `<?xml version="1.0" encoding="ISO-8859-1"?>`
+fn declared_encoding(start: &[u8]) -> Option<&[u8]> {
+    let start = &start[..start.len().min(DECLARATION_LENGTH)];
+    let declaration = start.strip_prefix(b"<?xml")?;
+    let declaration = &declaration[..declaration.windows(2).position(|end| end == b"?>")?];
+
+    let position = declaration.windows(8).position(|name| name == b"encoding")?;
+    let rest = declaration[position + 8..].trim_ascii_start().strip_prefix(b"=")?.trim_ascii_start();
+    let quote = *rest.first().filter(|quote| **quote == b'"' || **quote == b'\'')?;
+    let value = &rest[1..];
+    Some(&value[..value.iter().position(|byte| *byte == quote)?])
+}
+
+/// converts the content of a document in another encoding than UTF-8 into text
+pub fn decode(content: &[u8], encoding: &'static Encoding) -> Result<String, Error> {
+    let bom_length = match Encoding::for_bom(content) {
+        Some((bom_encoding, bom_length)) if bom_encoding == encoding => bom_length,
+        _ => 0,
+    };
+    encoding.decode_without_bom_handling_and_without_replacement(&content[bom_length..])
+        .map(|text| text.into_owned())
+        .ok_or_else(|| anyhow!("the content is not valid {}", encoding.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::WINDOWS_1252;
+
+    use super::*;
+
+    const TEXT: &str = "<?xml version=\"1.0\"?><A>Größe 😀</A>";
+
+    fn utf_16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() })
+            .collect()
+    }
+
+    #[test]
+    fn utf_16_with_byte_order_mark() {
+        for big_endian in [false, true] {
+            let content = utf_16(&format!("\u{feff}{}", TEXT), big_endian);
+            let encoding = detect(&content);
+            assert_eq!(encoding, if big_endian { UTF_16BE } else { UTF_16LE });
+            // the byte order mark isn't part of the text
+            assert_eq!(decode(&content, encoding).unwrap(), TEXT);
+        }
+    }
+
+    #[test]
+    fn utf_16_without_byte_order_mark() {
+        assert_eq!(detect(&utf_16(TEXT, false)), UTF_16LE);
+        assert_eq!(detect(&utf_16(TEXT, true)), UTF_16BE);
+    }
+
+    #[test]
+    fn declared_encoding_is_used() {
+        let content = b"<?xml version=\"1.0\" encoding=\"windows-1252\"?><A>Gr\xf6\xdfe \x80</A>";
+        let encoding = detect(content);
+        assert_eq!(encoding, WINDOWS_1252);
+        assert_eq!(decode(content, encoding).unwrap(), "<?xml version=\"1.0\" encoding=\"windows-1252\"?><A>Größe €</A>");
+
+        // ISO-8859-1 is decoded as windows-1252 like in browsers, the spaces and quotes of the declaration may vary
+        assert_eq!(detect(b"<?xml version='1.0' encoding = 'ISO-8859-1' ?><A/>"), WINDOWS_1252);
+    }
+
+    #[test]
+    fn utf_8_is_the_fallback() {
+        // neither a byte order mark nor a declaration
+        assert_eq!(detect(TEXT.as_bytes()), UTF_8);
+        assert_eq!(detect(b"<A/>"), UTF_8);
+        // an unknown encoding
+        assert_eq!(detect(b"<?xml version=\"1.0\" encoding=\"no-encoding\"?><A/>"), UTF_8);
+        // a declaration which could be read as ASCII can't be UTF-16
+        assert_eq!(detect(b"<?xml version=\"1.0\" encoding=\"UTF-16\"?><A/>"), UTF_8);
+        // a declaration without an end
+        assert_eq!(detect(b"<?xml version=\"1.0\" encoding=\"windows-1252\""), UTF_8);
+    }
+
+    #[test]
+    fn malformed_content_is_an_error() {
+        // a high surrogate without the low surrogate
+        assert!(decode(&[0x3c, 0x00, 0x3d, 0xd8, 0x3e, 0x00], UTF_16LE).is_err());
+    }
+}
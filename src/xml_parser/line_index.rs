@@ -0,0 +1,341 @@
+use std::ops::Range;
+
+use roxmltree::TextPos;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Position, PositionEncodingKind};
+
+/// encoding the client counts the characters of a position in
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PositionEncoding {
+    Utf8,
+    /// the encoding every client supports, used if the client doesn't offer another one
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// picks the first encoding the client offers which is supported
+    pub fn negotiate(encodings: Option<&[PositionEncodingKind]>) -> Self {
+        encodings.unwrap_or_default().iter()
+            .find_map(|encoding| match encoding.as_str() {
+                "utf-8" => Some(PositionEncoding::Utf8),
+                "utf-16" => Some(PositionEncoding::Utf16),
+                "utf-32" => Some(PositionEncoding::Utf32),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+            PositionEncoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    /// the number of code units of a character with the given UTF-8 length
+    fn units(self, len_utf8: u8) -> usize {
+        match self {
+            PositionEncoding::Utf8 => len_utf8 as usize,
+            PositionEncoding::Utf16 => if len_utf8 == 4 { 2 } else { 1 },
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+}
+
+/// converts between byte offsets into a text and LSP positions. Only the characters outside of ASCII are kept
+/// besides the line offsets, the columns of all other characters are the same in every encoding.
+#[derive(Serialize, Deserialize)]
+pub struct LineIndex {
+    pub(super) line_offsets: Vec<usize>,
+    /// byte offset and UTF-8 length of every character outside of ASCII
+    pub(super) wide_chars: Vec<(usize, u8)>,
+    /// byte offset of every carriage return, the end of a CRLF line is in front of it
+    pub(super) carriage_returns: Vec<usize>,
+}
+
+impl Default for LineIndex {
+    /// the index of an empty text
+    fn default() -> Self {
+        LineIndex { line_offsets: vec![0], wide_chars: Vec::new(), carriage_returns: Vec::new() }
+    }
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_index = LineIndex::default();
+        line_index.add(text, 0);
+        line_index
+    }
+
+    /// adds the lines and characters of text which starts at the offset
+    fn add(&mut self, text: &str, offset: usize) {
+        for (index, c) in text.char_indices() {
+            if c == '\n' {
+                self.line_offsets.push(offset + index + 1);
+            } else if c == '\r' {
+                self.carriage_returns.push(offset + index);
+            } else if !c.is_ascii() {
+                self.wide_chars.push((offset + index, c.len_utf8() as u8));
+            }
+        }
+    }
+
+    /// adds the characters of the bytes which start at the offset, the bytes have to be valid UTF-8 but may start
+    /// or end inside of a character
+    pub(super) fn add_bytes(&mut self, bytes: &[u8], offset: usize) {
+        for (index, byte) in bytes.iter().enumerate() {
+            match byte {
+                b'\n' => self.line_offsets.push(offset + index + 1),
+                b'\r' => self.carriage_returns.push(offset + index),
+                // continuation bytes are 0b10xxxxxx, only the first byte of a character is counted
+                0xC0..=0xDF => self.wide_chars.push((offset + index, 2)),
+                0xE0..=0xEF => self.wide_chars.push((offset + index, 3)),
+                0xF0..=0xFF => self.wide_chars.push((offset + index, 4)),
+                _ => (),
+            }
+        }
+    }
+
+    /// replaces the lines and characters of the text between `start` and `old_end` with the ones of the inserted text
+    pub fn patch(&mut self, start: usize, old_end: usize, inserted: &str) {
+        let delta = inserted.len() as isize - (old_end - start) as isize;
+        let shift = |offset: usize| (offset as isize + delta) as usize;
+
+        let first = self.line_offsets.partition_point(|&offset| offset <= start);
+        let last = self.line_offsets.partition_point(|&offset| offset <= old_end);
+        let mut moved = self.line_offsets.split_off(last);
+        self.line_offsets.truncate(first);
+        moved.iter_mut().for_each(|offset| *offset = shift(*offset));
+
+        let first = self.wide_chars.partition_point(|&(offset, _)| offset < start);
+        let last = self.wide_chars.partition_point(|&(offset, _)| offset < old_end);
+        let mut moved_chars = self.wide_chars.split_off(last);
+        self.wide_chars.truncate(first);
+        moved_chars.iter_mut().for_each(|(offset, _)| *offset = shift(*offset));
+
+        let first = self.carriage_returns.partition_point(|&offset| offset < start);
+        let last = self.carriage_returns.partition_point(|&offset| offset < old_end);
+        let mut moved_returns = self.carriage_returns.split_off(last);
+        self.carriage_returns.truncate(first);
+        moved_returns.iter_mut().for_each(|offset| *offset = shift(*offset));
+
+        self.add(inserted, start);
+        self.line_offsets.append(&mut moved);
+        self.wide_chars.append(&mut moved_chars);
+        self.carriage_returns.append(&mut moved_returns);
+    }
+
+    /// returns the line and the byte column, both starting at 1, of the start and end of the range
+    pub fn text_pos(&self, range: Range<usize>) -> (TextPos, TextPos) {
+        let pos = |offset: usize| {
+            let line = self.line_offsets.partition_point(|&x| x <= offset);
+            TextPos::new(line as u32, (offset - self.line_offsets[line - 1] + 1) as u32)
+        };
+        (pos(range.start), pos(range.end))
+    }
+
+    /// converts a byte offset into a position
+    pub fn position(&self, offset: usize, encoding: PositionEncoding) -> Position {
+        let line = self.line_offsets.partition_point(|&x| x <= offset) - 1;
+        let line_start = self.line_offsets[line];
+
+        let first = self.wide_chars.partition_point(|&(wide, _)| wide < line_start);
+        let last = self.wide_chars.partition_point(|&(wide, _)| wide < offset);
+        let surplus: usize = self.wide_chars[first..last].iter()
+            .map(|&(_, len)| len as usize - encoding.units(len))
+            .sum();
+
+        Position::new(line as u32, (offset - line_start - surplus) as u32)
+    }
+
+    pub fn range(&self, range: &Range<usize>, encoding: PositionEncoding) -> tower_lsp::lsp_types::Range {
+        tower_lsp::lsp_types::Range::new(self.position(range.start, encoding), self.position(range.end, encoding))
+    }
+
+    /// converts the range into one range per line, for clients which don't support ranges across lines. The line
+    /// ends aren't part of the ranges.
+    pub fn line_ranges(&self, range: &Range<usize>, encoding: PositionEncoding) -> Vec<tower_lsp::lsp_types::Range> {
+        let start = self.position(range.start, encoding);
+        let end = self.position(range.end, encoding);
+
+        (start.line..=end.line).map(|line| {
+            let line_start = if line == start.line { start } else { Position::new(line, 0) };
+            let line_end = match self.line_end(line as usize) {
+                Some(line_end) if line != end.line => self.position(line_end, encoding),
+                _ => end,
+            };
+            tower_lsp::lsp_types::Range::new(line_start, line_end)
+        }).collect()
+    }
+
+    /// converts a position into a byte offset, `None` if the line doesn't exist. A position behind the end of the
+    /// line is moved to the line end, one inside of a character behind the character.
+    pub fn offset(&self, position: Position, encoding: PositionEncoding) -> Option<usize> {
+        let line = position.line as usize;
+        let line_start = *self.line_offsets.get(line)?;
+
+        let first = self.wide_chars.partition_point(|&(wide, _)| wide < line_start);
+        let mut offset = line_start + position.character as usize;
+        for &(wide, len) in &self.wide_chars[first..] {
+            if wide >= offset {
+                break;
+            }
+            offset = (offset + len as usize - encoding.units(len)).max(wide + len as usize);
+        }
+
+        match self.line_end(line) {
+            Some(line_end) => Some(offset.min(line_end)),
+            None => Some(offset),
+        }
+    }
+
+    /// returns the offset of the line break which ends the line, `None` for the last line
+    fn line_end(&self, line: usize) -> Option<usize> {
+        let line_feed = self.line_offsets.get(line + 1)? - 1;
+        if line_feed > 0 && self.carriage_returns.binary_search(&(line_feed - 1)).is_ok() {
+            Some(line_feed - 1)
+        } else {
+            Some(line_feed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODINGS: [PositionEncoding; 3] = [PositionEncoding::Utf8, PositionEncoding::Utf16, PositionEncoding::Utf32];
+
+    /// umlauts are two bytes and one UTF-16 unit, the emoji is four bytes and a UTF-16 surrogate pair
+    const TEXT: &str = "<A>Größe</A>\n<B>😀x</B>\n\nä😀ü";
+
+    fn assert_same(patched: &LineIndex, text: &str) {
+        let fresh = LineIndex::new(text);
+        assert_eq!(patched.line_offsets, fresh.line_offsets, "line offsets of {:?}", text);
+        assert_eq!(patched.wide_chars, fresh.wide_chars, "wide characters of {:?}", text);
+        assert_eq!(patched.carriage_returns, fresh.carriage_returns, "carriage returns of {:?}", text);
+    }
+
+    #[test]
+    fn columns_depend_on_the_encoding() {
+        let line_index = LineIndex::new(TEXT);
+        let behind = |pattern: &str| TEXT.find(pattern).unwrap() + pattern.len();
+
+        // behind "Grö"
+        let offset = behind("Grö");
+        assert_eq!(line_index.position(offset, PositionEncoding::Utf8), Position::new(0, 7));
+        assert_eq!(line_index.position(offset, PositionEncoding::Utf16), Position::new(0, 6));
+        assert_eq!(line_index.position(offset, PositionEncoding::Utf32), Position::new(0, 6));
+
+        // behind the emoji
+        let offset = behind("<B>😀");
+        assert_eq!(line_index.position(offset, PositionEncoding::Utf8), Position::new(1, 7));
+        assert_eq!(line_index.position(offset, PositionEncoding::Utf16), Position::new(1, 5));
+        assert_eq!(line_index.position(offset, PositionEncoding::Utf32), Position::new(1, 4));
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        let line_index = LineIndex::new(TEXT);
+        for encoding in ENCODINGS {
+            for offset in (0..=TEXT.len()).filter(|&offset| TEXT.is_char_boundary(offset)) {
+                let position = line_index.position(offset, encoding);
+                assert_eq!(line_index.offset(position, encoding), Some(offset), "{:?} at {:?}", encoding, position);
+            }
+        }
+    }
+
+    #[test]
+    fn offset_inside_of_a_character_is_moved_behind_it() {
+        let line_index = LineIndex::new(TEXT);
+        let emoji = TEXT.find('😀').unwrap();
+
+        // between the units of the surrogate pair
+        assert_eq!(line_index.offset(Position::new(1, 4), PositionEncoding::Utf16), Some(emoji + 4));
+        assert_eq!(line_index.offset(Position::new(1, 5), PositionEncoding::Utf8), Some(emoji + 4));
+        // between the bytes of the umlaut
+        let umlaut = TEXT.find('ö').unwrap();
+        assert_eq!(line_index.offset(Position::new(0, 6), PositionEncoding::Utf8), Some(umlaut + 2));
+    }
+
+    #[test]
+    fn offset_behind_the_line_end_is_clamped() {
+        let line_index = LineIndex::new(TEXT);
+        for encoding in ENCODINGS {
+            assert_eq!(line_index.offset(Position::new(0, 100), encoding), Some(TEXT.find('\n').unwrap()));
+            assert_eq!(line_index.offset(Position::new(2, 1), encoding), Some(TEXT.find("\n\n").unwrap() + 1));
+            assert_eq!(line_index.offset(Position::new(4, 0), encoding), None);
+        }
+    }
+
+    #[test]
+    fn patch_matches_a_new_index() {
+        // the replaced text is the first occurrence in TEXT
+        let edits = [
+            ("", "😀\n"),
+            ("ße</A>\n<B>", "ss\nä"),
+            ("\n\n", ""),
+            ("\n\nä", "\r\n"),
+            ("😀x</B>\n", "x"),
+            ("Größe</A>\n<B>😀x</B>\n\nä😀ü", "ü\nü\n"),
+            ("ü", ""),
+        ];
+        for (replaced, inserted) in edits {
+            let start = TEXT.find(replaced).unwrap();
+            let old_end = start + replaced.len();
+            let mut line_index = LineIndex::new(TEXT);
+            line_index.patch(start, old_end, inserted);
+
+            let mut text = TEXT.to_string();
+            text.replace_range(start..old_end, inserted);
+            assert_same(&line_index, &text);
+        }
+    }
+
+    #[test]
+    fn patches_in_sequence_match_a_new_index() {
+        let mut text = TEXT.to_string();
+        let mut line_index = LineIndex::new(&text);
+        for (start, old_end, inserted) in [(0, 0, "ä\n"), (3, 20, "\n😀\n"), (9, 9, "\r\n"), (2, 12, "")] {
+            line_index.patch(start, old_end, inserted);
+            text.replace_range(start..old_end, inserted);
+            assert_same(&line_index, &text);
+        }
+    }
+
+    #[test]
+    fn crlf_line_ends() {
+        let text = "<A>ä</A>\r\n<B/>\r\n";
+        let line_index = LineIndex::new(text);
+        let cr = text.find('\r').unwrap();
+
+        assert_eq!(line_index.line_offsets, vec![0, cr + 2, text.len()]);
+        for encoding in ENCODINGS {
+            let position = line_index.position(cr, encoding);
+            assert_eq!(line_index.offset(position, encoding), Some(cr));
+            // the carriage return is the last character of the line
+            assert_eq!(line_index.position(cr + 1, encoding), Position::new(0, position.character + 1));
+            assert_eq!(line_index.position(cr + 2, encoding), Position::new(1, 0));
+            // positions behind the line end and inside of the line break are moved in front of it
+            assert_eq!(line_index.offset(Position::new(0, 100), encoding), Some(cr));
+            assert_eq!(line_index.offset(Position::new(0, position.character + 1), encoding), Some(cr));
+        }
+    }
+
+    #[test]
+    fn line_ranges_end_in_front_of_the_line_break() {
+        for line_break in ["\n", "\r\n"] {
+            let text = ["<VALUE>ä", "😀", "x</VALUE>"].join(line_break);
+            let line_index = LineIndex::new(&text);
+            let ranges = line_index.line_ranges(&(0..text.len()), PositionEncoding::Utf16);
+            assert_eq!(ranges, vec![
+                tower_lsp::lsp_types::Range::new(Position::new(0, 0), Position::new(0, 8)),
+                tower_lsp::lsp_types::Range::new(Position::new(1, 0), Position::new(1, 2)),
+                tower_lsp::lsp_types::Range::new(Position::new(2, 0), Position::new(2, 9)),
+            ], "{:?}", line_break);
+        }
+    }
+}
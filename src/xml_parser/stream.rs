@@ -0,0 +1,704 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{BufRead, Read};
+use std::ops::Range;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::PrefixDeclaration;
+use quick_xml::Reader;
+
+use super::{get_value_kind, is_block, is_ecuc_def, is_param_def, is_value, is_variant, IdentNode, LineIndex, MultiplicityBounds, ParamDef, RefNode, SharedStr, ValueNode, XmlParser, XmlParserNode};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// the document can't be indexed while it is read, it has to be parsed as a whole. That is the case for documents
+/// which aren't well-formed, which have a DTD, or whose short names aren't the first child of their element.
+struct Unsupported;
+
+impl XmlParser {
+    /// indexes the document while it is read, without keeping the document in memory. Returns `false` if the
+    /// document has to be parsed as a whole instead, the parser is empty then.
+    pub fn parse_stream<R: BufRead>(&mut self, reader: R) -> bool {
+        let indexed = Stream::new(self).read(reader).is_ok();
+        if !indexed {
+            self.ident_nodes.clear();
+            self.duplicate_nodes.clear();
+            self.refs.clear();
+            self.blocks.clear();
+            self.vendor_mapping = None;
+            self.line_index = LineIndex::default();
+        }
+        self.last_ident_node = None;
+        self.strings = HashSet::new();
+        indexed
+    }
+}
+
+/// reader which collects the lines and characters and checks the encoding of the bytes passing through it
+struct LineReader<R> {
+    reader: R,
+    offset: usize,
+    /// lines and characters which were read since they were taken the last time
+    line_index: LineIndex,
+    utf8: Utf8Check,
+}
+
+impl<R: BufRead> LineReader<R> {
+    fn new(reader: R) -> Self {
+        LineReader { reader, offset: 0, line_index: LineIndex { line_offsets: Vec::new(), wide_chars: Vec::new(), carriage_returns: Vec::new() }, utf8: Utf8Check::default() }
+    }
+}
+
+/// checks the encoding of text which is read in parts
+#[derive(Default)]
+struct Utf8Check {
+    /// start of a character which continues in the next part
+    incomplete: Vec<u8>,
+    invalid: bool,
+}
+
+impl Utf8Check {
+    fn check(&mut self, mut bytes: &[u8]) {
+        while !self.incomplete.is_empty() && !bytes.is_empty() && !self.invalid {
+            self.incomplete.push(bytes[0]);
+            bytes = &bytes[1..];
+            match std::str::from_utf8(&self.incomplete) {
+                Ok(_) => self.incomplete.clear(),
+                Err(e) => self.invalid = e.error_len().is_some() || self.incomplete.len() == 4,
+            }
+        }
+        if self.invalid || !self.incomplete.is_empty() {
+            return;
+        }
+        if let Err(e) = std::str::from_utf8(bytes) {
+            match e.error_len() {
+                Some(_) => self.invalid = true,
+                None => self.incomplete.extend_from_slice(&bytes[e.valid_up_to()..]),
+            }
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.invalid && self.incomplete.is_empty()
+    }
+}
+
+impl<R: BufRead> Read for LineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for LineReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // the bytes are still buffered, so this doesn't read anything
+        if let Ok(buf) = self.reader.fill_buf() {
+            let bytes = &buf[..amt.min(buf.len())];
+            self.line_index.add_bytes(bytes, self.offset);
+            self.utf8.check(bytes);
+        }
+        self.offset += amt;
+        self.reader.consume(amt);
+    }
+}
+
+/// the element which will be indexed next or the last one which was indexed. Values are added to it, like in
+/// `XmlParser::traverse_xml`.
+#[derive(Clone)]
+enum IdentTarget {
+    /// index of the element in the stack
+    Open(usize),
+    Closed(SharedStr),
+}
+
+/// an identifiable element whose short name was read
+struct OpenIdent {
+    short_name: String,
+    short_name_range: Range<usize>,
+    values: Vec<ValueNode>,
+    param_def: Option<ParamDef>,
+}
+
+enum FirstChild {
+    /// the text and the range of its first part
+    Text(String, Range<usize>),
+    /// any other node, the range of an element is known once it ends
+    Node(Option<Range<usize>>),
+}
+
+/// what the parent of an element needs to know about it
+struct ChildElement {
+    range: Range<usize>,
+    text: Option<String>,
+    first_child_range: Option<Range<usize>>,
+    empty_content_range: Option<Range<usize>>,
+    dest: Option<String>,
+}
+
+struct OpenElement {
+    tag_name: SharedStr,
+    start: usize,
+    /// empty range behind the start tag, if the element isn't empty
+    content_range: Option<Range<usize>>,
+    /// the path of the nodes inside the element
+    path: SharedStr,
+    prefixes: Vec<Vec<u8>>,
+    first_child: Option<FirstChild>,
+    elements: usize,
+    has_short_name: bool,
+    ident: Option<Box<OpenIdent>>,
+    dest: Option<(String, Range<usize>)>,
+    /// the element the value is added to and its index in the values
+    value: Option<(Option<IdentTarget>, usize)>,
+    def_ref: Option<ChildElement>,
+    value_child: Option<ChildElement>,
+    value_ref_child: Option<ChildElement>,
+    multiplicity: MultiplicityBounds,
+    desc: Option<Option<String>>,
+}
+
+struct Stream<'a> {
+    parser: &'a mut XmlParser,
+    stack: Vec<OpenElement>,
+    line_index: LineIndex,
+    /// text and CDATA which are read one after another form a single text node
+    text: Option<(String, Range<usize>)>,
+    last_ident: Option<IdentTarget>,
+    /// the DESC element whose words are collected and the words collected so far
+    desc: Option<(usize, String)>,
+    has_root: bool,
+    /// the duplicates with the start of the node which replaced them, to order them like `traverse_xml` does
+    duplicates: Vec<(usize, IdentNode)>,
+}
+
+impl<'a> Stream<'a> {
+    fn new(parser: &'a mut XmlParser) -> Self {
+        Stream {
+            parser,
+            stack: Vec::new(),
+            line_index: LineIndex::default(),
+            text: None,
+            last_ident: None,
+            desc: None,
+            has_root: false,
+            duplicates: Vec::new(),
+        }
+    }
+
+    fn read<R: BufRead>(mut self, reader: R) -> Result<(), Unsupported> {
+        let mut reader = LineReader::new(reader);
+        // the positions of the events don't include the byte order mark
+        let bom = match reader.fill_buf() {
+            Ok(buf) if buf.starts_with(UTF8_BOM) => UTF8_BOM.len(),
+            Ok(_) => 0,
+            Err(_) => return Err(Unsupported),
+        };
+        reader.consume(bom);
+
+        let mut reader = Reader::from_reader(reader);
+        let mut buf = Vec::new();
+        loop {
+            let start = bom + reader.buffer_position();
+            let event = reader.read_event_into(&mut buf).map_err(|_| Unsupported)?;
+            let range = start..bom + reader.buffer_position();
+            let read = &mut reader.get_mut().line_index;
+            self.line_index.line_offsets.append(&mut read.line_offsets);
+            self.line_index.wide_chars.append(&mut read.wide_chars);
+            self.line_index.carriage_returns.append(&mut read.carriage_returns);
+
+            match event {
+                Event::Text(text) => self.add_text(&unescape(&text, false)?, range),
+                Event::CData(cdata) => self.add_text(&normalize_line_ends(to_str(&cdata)?, false), range),
+                Event::Start(element) => {
+                    self.end_text()?;
+                    self.start_element(&element, range, false)?;
+                },
+                Event::Empty(element) => {
+                    self.end_text()?;
+                    let end = range.end;
+                    self.start_element(&element, range, true)?;
+                    self.end_element(end)?;
+                },
+                Event::End(_) => {
+                    self.end_text()?;
+                    self.end_element(range.end)?;
+                },
+                Event::Comment(_) | Event::PI(_) => {
+                    self.end_text()?;
+                    if let Some(parent) = self.stack.last_mut() {
+                        parent.first_child.get_or_insert(FirstChild::Node(Some(range)));
+                    }
+                },
+                Event::Decl(_) => (),
+                Event::DocType(_) => return Err(Unsupported),
+                Event::Eof => break,
+            }
+
+            buf.clear();
+        }
+        self.end_text()?;
+
+        if !reader.get_ref().utf8.is_valid() || !self.has_root || !self.stack.is_empty() {
+            return Err(Unsupported);
+        }
+
+        self.duplicates.sort_by_key(|(start, _)| *start);
+        self.parser.duplicate_nodes.extend(self.duplicates.into_iter().map(|(_, node)| node));
+        self.parser.line_index = self.line_index;
+        Ok(())
+    }
+
+    fn add_text(&mut self, text: &str, range: Range<usize>) {
+        if text.is_empty() {
+            return;
+        }
+        match &mut self.text {
+            Some((content, _)) => content.push_str(text),
+            None => self.text = Some((text.to_string(), range)),
+        }
+    }
+
+    /// adds the text which was read since the last node to its element
+    fn end_text(&mut self) -> Result<(), Unsupported> {
+        let (text, range) = match self.text.take() {
+            Some(text) => text,
+            None => return Ok(()),
+        };
+        let element = match self.stack.last_mut() {
+            Some(element) => element,
+            // only whitespace is allowed around the root element
+            None if text.trim().is_empty() => return Ok(()),
+            None => return Err(Unsupported),
+        };
+
+        if let Some((_, desc)) = &mut self.desc {
+            for word in text.split_whitespace() {
+                if !desc.is_empty() {
+                    desc.push(' ');
+                }
+                desc.push_str(word);
+            }
+        }
+
+        if element.first_child.is_none() {
+            element.first_child = Some(FirstChild::Text(text, range));
+        }
+        Ok(())
+    }
+
+    fn start_element(&mut self, element: &BytesStart, range: Range<usize>, empty: bool) -> Result<(), Unsupported> {
+        let tag_name = self.parser.intern(to_str(element.local_name().as_ref())?);
+
+        let mut prefixes = Vec::new();
+        let mut dest = None;
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(|_| Unsupported)?;
+            if let Some(PrefixDeclaration::Named(prefix)) = attribute.key.as_namespace_binding() {
+                prefixes.push(prefix.to_vec());
+            } else if attribute.key.as_ref() == b"DEST" {
+                // the range is the one of the raw value between the quotes, like the one of `get_attribute_value_range`
+                let offset = match &attribute.value {
+                    Cow::Borrowed(value) => value.as_ptr() as usize - element.as_ptr() as usize,
+                    Cow::Owned(_) => return Err(Unsupported),
+                };
+                let value = unescape(&attribute.value, true)?;
+                let start = range.start + 1 + offset;
+                dest = Some((value.to_string(), start..start + attribute.value.len()));
+            }
+        }
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(|_| Unsupported)?;
+            if attribute.key.as_namespace_binding().is_none() {
+                if let Some(prefix) = attribute.key.prefix() {
+                    self.check_prefix(prefix.as_ref(), &prefixes)?;
+                }
+            }
+        }
+        if let Some(prefix) = element.name().prefix() {
+            self.check_prefix(prefix.as_ref(), &prefixes)?;
+        }
+
+        let depth = self.stack.len();
+        let path = match self.stack.last_mut() {
+            Some(parent) => {
+                // the path of the nodes in the element has to be known when they are read
+                if *parent.tag_name == *"SHORT-NAME" {
+                    return Err(Unsupported);
+                }
+                if *tag_name == *"SHORT-NAME" {
+                    if parent.elements > 0 && !parent.has_short_name {
+                        return Err(Unsupported);
+                    }
+                } else if parent.elements == 0 && parent.dest.is_some() {
+                    return Err(Unsupported);
+                }
+                if *tag_name == *"DESC" && self.desc.is_none() && parent.desc.is_none() && parent.ident.is_some() && is_ecuc_def(&parent.tag_name) {
+                    self.desc = Some((depth, String::new()));
+                }
+                parent.elements += 1;
+                parent.first_child.get_or_insert(FirstChild::Node(None));
+                parent.path.clone()
+            },
+            None if self.has_root => return Err(Unsupported),
+            None => {
+                self.has_root = true;
+                SharedStr::from("")
+            },
+        };
+
+        // `traverse_xml` decides if an element is a value when it starts
+        let value = if dest.is_none() && is_value(&tag_name) {
+            let index = match &self.last_ident {
+                Some(IdentTarget::Open(index)) => self.stack[*index].ident.as_ref().unwrap().values.len(),
+                Some(IdentTarget::Closed(path)) => self.parser.ident_nodes[path].values.len(),
+                None => 0,
+            };
+            Some((self.last_ident.clone(), index))
+        } else {
+            None
+        };
+
+        // like `get_empty_content_range`, the content starts behind the first `>`
+        let content_range = if empty {
+            None
+        } else {
+            let content_start = match element.iter().position(|byte| *byte == b'>') {
+                Some(index) => range.start + index + 2,
+                None => range.end,
+            };
+            Some(content_start..content_start)
+        };
+
+        self.stack.push(OpenElement {
+            tag_name,
+            start: range.start,
+            content_range,
+            path,
+            prefixes,
+            first_child: None,
+            elements: 0,
+            has_short_name: false,
+            ident: None,
+            dest,
+            value,
+            def_ref: None,
+            value_child: None,
+            value_ref_child: None,
+            multiplicity: MultiplicityBounds::default(),
+            desc: None,
+        });
+        Ok(())
+    }
+
+    fn check_prefix(&self, prefix: &[u8], prefixes: &[Vec<u8>]) -> Result<(), Unsupported> {
+        let declared = |prefixes: &[Vec<u8>]| prefixes.iter().any(|declared| declared == prefix);
+        if prefix == b"xml" || declared(prefixes) || self.stack.iter().any(|element| declared(&element.prefixes)) {
+            Ok(())
+        } else {
+            Err(Unsupported)
+        }
+    }
+
+    fn end_element(&mut self, end: usize) -> Result<(), Unsupported> {
+        let mut element = self.stack.pop().ok_or(Unsupported)?;
+        let depth = self.stack.len();
+        let range = element.start..end;
+
+        let (text, first_child_range) = match element.first_child.take() {
+            Some(FirstChild::Text(text, range)) => (Some(text), Some(range)),
+            Some(FirstChild::Node(range)) => (None, range),
+            None => (None, None),
+        };
+
+        if let Some(ident) = element.ident.take() {
+            self.add_ident(&element, *ident, range.clone());
+            if let Some(IdentTarget::Open(index)) = self.last_ident {
+                if index == depth {
+                    self.last_ident = Some(IdentTarget::Closed(element.path.clone()));
+                }
+            }
+        } else if element.has_short_name {
+            // an element with a short name without text is neither a reference nor a value
+        } else if let Some((dest, dest_range)) = &element.dest {
+            let text_range = match (&text, &first_child_range) {
+                (Some(_), Some(text_range)) => Some(text_range.clone()),
+                _ => element.content_range.clone(),
+            };
+            match text_range {
+                Some(text_range) => self.add_ref(&element, text.as_deref().unwrap_or(""), text_range, dest, dest_range.clone(), range.clone()),
+                None => {
+                    let start_pos = self.line_index.text_pos(range.clone()).0;
+                    eprintln!("ERROR: No text found for ref node: {}:{}:{}", self.parser.file, start_pos.row, start_pos.col);
+                },
+            }
+        } else if let Some((target, index)) = element.value.take() {
+            self.add_value(&element, target, index, range.clone())?;
+        }
+
+        if is_block(&element.tag_name) {
+            self.parser.blocks.push(range.clone());
+        }
+
+        if let Some((desc_depth, desc)) = self.desc.take() {
+            if desc_depth == depth {
+                if let Some(parent) = self.stack.last_mut() {
+                    parent.desc = Some(if desc.is_empty() { None } else { Some(desc) });
+                }
+            } else {
+                self.desc = Some((desc_depth, desc));
+            }
+        }
+
+        if let Some(text) = &text {
+            // the constraints of a parameter definition may be nested in *-VARIANTS/*-CONDITIONAL elements
+            for ancestor in self.stack.iter_mut().rev() {
+                if let Some(param_def) = ancestor.ident.as_mut().and_then(|ident| ident.param_def.as_mut()) {
+                    param_def.set(&element.tag_name, text);
+                }
+                if !is_variant(&ancestor.tag_name) {
+                    break;
+                }
+            }
+        }
+
+        let parent = match self.stack.last_mut() {
+            Some(parent) => parent,
+            None => return Ok(()),
+        };
+        if let Some(FirstChild::Node(first_child_range @ None)) = &mut parent.first_child {
+            *first_child_range = Some(range.clone());
+        }
+
+        let tag_name = element.tag_name;
+        parent.multiplicity.set(&tag_name, text.as_deref());
+        let child = ChildElement {
+            range,
+            text,
+            first_child_range,
+            empty_content_range: element.content_range,
+            dest: element.dest.map(|(dest, _)| dest),
+        };
+        match &*tag_name {
+            "SHORT-NAME" if !parent.has_short_name => {
+                parent.has_short_name = true;
+                match child.text {
+                    Some(short_name) => {
+                        parent.path = SharedStr::from(format!("{}/{}", parent.path, short_name));
+                        parent.ident = Some(Box::new(OpenIdent {
+                            short_name,
+                            short_name_range: child.first_child_range.unwrap(),
+                            values: Vec::new(),
+                            param_def: if is_param_def(&parent.tag_name) { Some(ParamDef::default()) } else { None },
+                        }));
+                        self.last_ident = Some(IdentTarget::Open(depth - 1));
+                    },
+                    None => {
+                        let start_pos = self.line_index.text_pos(child.range).0;
+                        eprintln!("ERROR: No text found for node: {}:{}:{}", self.parser.file, start_pos.row, start_pos.col);
+                    },
+                }
+            },
+            "DEFINITION-REF" if parent.def_ref.is_none() => parent.def_ref = Some(child),
+            "VALUE" if parent.value_child.is_none() => parent.value_child = Some(child),
+            "VALUE-REF" if parent.value_ref_child.is_none() => parent.value_ref_child = Some(child),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn add_ident(&mut self, element: &OpenElement, ident: OpenIdent, range: Range<usize>) {
+        let tag_name = &*element.tag_name;
+        let def_ref = if tag_name == "ECUC-CONTAINER-VALUE" || tag_name == "ECUC-MODULE-CONFIGURATION-VALUES" {
+            match &element.def_ref {
+                Some(ChildElement { text: Some(def_ref_text), .. }) => Some(self.parser.intern(def_ref_text)),
+                Some(def_ref) => {
+                    let start_pos = self.line_index.text_pos(def_ref.range.clone()).0;
+                    eprintln!("ERROR: No text found for node: {}:{}:{}", self.parser.file, start_pos.row, start_pos.col);
+                    None
+                },
+                None => None,
+            }
+        } else {
+            None
+        };
+
+        let (start, end) = self.line_index.text_pos(range.clone());
+        let (short_name_start, short_name_end) = self.line_index.text_pos(ident.short_name_range.clone());
+        let node = IdentNode {
+            short_name: ident.short_name,
+            node: XmlParserNode {
+                file: self.parser.file.clone(),
+                start,
+                end,
+                range: range.clone(),
+                tag_name: element.tag_name.clone(),
+                def_ref,
+            },
+            short_name_start,
+            short_name_end,
+            short_name_range: ident.short_name_range,
+            path: element.path.clone(),
+            values: ident.values,
+            multiplicity: element.multiplicity.multiplicity(),
+            param_def: ident.param_def,
+            desc: if is_ecuc_def(tag_name) { element.desc.clone().flatten() } else { None },
+        };
+        if let Some(duplicate) = self.parser.ident_nodes.insert(element.path.clone(), node) {
+            self.duplicates.push((range.start, duplicate));
+        }
+    }
+
+    fn add_ref(&mut self, element: &OpenElement, text: &str, text_range: Range<usize>, dest: &str, dest_range: Range<usize>, range: Range<usize>) {
+        let (start, end) = self.line_index.text_pos(range.clone());
+        let (text_start, text_end) = self.line_index.text_pos(text_range.clone());
+        let node = RefNode {
+            file: self.parser.file.clone(),
+            start,
+            end,
+            range,
+            tag_name: element.tag_name.clone(),
+            text: self.parser.intern(text),
+            text_start,
+            text_end,
+            text_range,
+            path: element.path.clone(),
+            dest: self.parser.intern(dest),
+            dest_range,
+        };
+        if let Some(ref_vec) = self.parser.refs.get_mut(text) {
+            ref_vec.push(node);
+        } else {
+            self.parser.refs.insert(node.text.clone(), vec![node]);
+        }
+        if *element.tag_name == *"REFINED-MODULE-DEF-REF" && !text.is_empty() {
+            self.parser.vendor_mapping = Some((element.path.to_string(), text.to_string()));
+            eprintln!("Vendor Mapping: {} -> {}", element.path, text);
+        }
+    }
+
+    fn add_value(&mut self, element: &OpenElement, target: Option<IdentTarget>, index: usize, range: Range<usize>) -> Result<(), Unsupported> {
+        let def_ref = match &element.def_ref {
+            Some(def_ref) => def_ref,
+            None => return Ok(()),
+        };
+        let def_ref_text = match &def_ref.text {
+            Some(def_ref_text) => def_ref_text,
+            None => {
+                let start_pos = self.line_index.text_pos(def_ref.range.clone()).0;
+                eprintln!("ERROR: No text found for node: {}:{}:{}", self.parser.file, start_pos.row, start_pos.col);
+                return Ok(());
+            },
+        };
+
+        let tag_name = &*element.tag_name;
+        let value = if tag_name == "ECUC-REFERENCE-VALUE" { &element.value_ref_child } else { &element.value_child };
+        // the range of a value without content is the value element itself
+        let value_range = match value {
+            Some(value) => match &value.first_child_range {
+                Some(first_child_range) => first_child_range.clone(),
+                None => value.empty_content_range.clone().unwrap_or(value.range.clone()),
+            },
+            None => range.clone(),
+        };
+        let value_text = value.as_ref().and_then(|value| value.text.as_deref()).unwrap_or("");
+
+        let (start, end) = self.line_index.text_pos(range.clone());
+        let value = ValueNode {
+            short_name: self.parser.intern(def_ref_text.rsplit('/').next().unwrap()),
+            node: XmlParserNode {
+                file: self.parser.file.clone(),
+                start,
+                end,
+                range,
+                tag_name: element.tag_name.clone(),
+                def_ref: Some(self.parser.intern(def_ref_text)),
+            },
+            value: self.parser.intern(value_text),
+            value_range,
+            typ: get_value_kind(tag_name, def_ref.dest.as_deref()),
+        };
+
+        let values = match target {
+            Some(IdentTarget::Open(target)) => &mut self.stack[target].ident.as_mut().unwrap().values,
+            Some(IdentTarget::Closed(path)) => &mut self.parser.ident_nodes.get_mut(&path).unwrap().values,
+            // the document has a value outside of any identifiable element
+            None => return Err(Unsupported),
+        };
+        values.insert(index, value);
+        Ok(())
+    }
+}
+
+fn to_str(bytes: &[u8]) -> Result<&str, Unsupported> {
+    std::str::from_utf8(bytes).map_err(|_| Unsupported)
+}
+
+/// line ends are read as `\n`, in attribute values all whitespace is read as space
+fn normalize_line_ends(text: &str, attribute: bool) -> Cow<'_, str> {
+    if !(text.contains('\r') || attribute && text.contains(['\n', '\t'])) {
+        return Cow::Borrowed(text);
+    }
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    Cow::Owned(if attribute { text.replace(['\n', '\t'], " ") } else { text })
+}
+
+fn unescape(bytes: &[u8], attribute: bool) -> Result<String, Unsupported> {
+    let text = normalize_line_ends(to_str(bytes)?, attribute);
+    quick_xml::escape::unescape(&text).map(|text| text.into_owned()).map_err(|_| Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use serde_json::Value;
+
+    use super::super::XmlParser;
+
+    /// contains the cases in which the parsers could differ, like text split by CDATA and comments, entities, line
+    /// ends and empty elements
+    const FIXTURE: &str = include_str!("../../examples/stream_equivalence.arxml");
+
+    fn value<T: Serialize>(node: &T) -> Value {
+        serde_json::to_value(node).unwrap()
+    }
+
+    #[test]
+    fn stream_matches_document() {
+        let mut document = XmlParser::new("stream_equivalence.arxml", true);
+        document.parse_document(FIXTURE).unwrap();
+        let mut stream = XmlParser::new("stream_equivalence.arxml", true);
+        assert!(stream.parse_stream(FIXTURE.as_bytes()));
+
+        assert_eq!(value(&document.ident_nodes), value(&stream.ident_nodes));
+        assert_eq!(value(&document.duplicate_nodes), value(&stream.duplicate_nodes));
+        assert_eq!(value(&document.refs), value(&stream.refs));
+        let blocks = |parser: &XmlParser| {
+            let mut blocks = parser.blocks.clone();
+            blocks.sort_by_key(|block| block.start);
+            blocks
+        };
+        assert_eq!(blocks(&document), blocks(&stream));
+        assert_eq!(value(&document.line_index), value(&stream.line_index));
+        assert_eq!(document.vendor_mapping, stream.vendor_mapping);
+    }
+
+    #[test]
+    fn constraints_are_read_from_variants_only() {
+        let mut stream = XmlParser::new("stream_equivalence.arxml", true);
+        assert!(stream.parse_stream(FIXTURE.as_bytes()));
+
+        // the DEFAULT-VALUE of the ADMIN-DATA in front of the variants is skipped
+        let param_def = stream.ident_nodes["/Pk&g/Mod/Cont/Int"].param_def.as_ref().unwrap();
+        assert_eq!(param_def.default_value.as_deref(), Some("3"));
+        assert_eq!((param_def.min.as_deref(), param_def.max.as_deref()), (Some("-1"), Some("10")));
+        assert_eq!(param_def.unit.as_deref(), Some("/Pk&g/Units/ms"));
+    }
+}